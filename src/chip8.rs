@@ -1,22 +1,63 @@
 use std::io;
-use std::io::Read;
+use std::path::PathBuf;
 use std::time;
 
+mod asm;
+mod audio;
+mod cdp1802;
+mod disasm;
 mod display;
+mod error;
+mod gif;
+mod graphics;
 mod keypad;
+mod layout;
+mod machine;
+mod movie;
+mod pacer;
+mod platform;
+mod quirks;
 mod rng;
+mod scheduler;
+mod sha1;
+mod shutdown;
 mod stack;
+mod status;
+mod timing;
+mod vip;
+mod watch;
+mod wav;
 
 const DISPLAY_WIDTH: usize = 64;
-const DISPLAY_HEIGHT: usize = 32;
 const MEM_SIZE: usize = 4096;
 const NUM_REGS: usize = 16;
 const REG_WIDTH: u8 = 8;
 const NUM_OPCODE_TYPES: usize = 16;
-const ROM_START_ADDR: usize = 512;
-const INSTR_PER_FRAME: u8 = 10; // Online consensus for ~10 instructions/frame
+const ROM_START_ADDR: usize = 512; // Before any --platform
+const CPU_HZ: u32 = 600; // Online consensus for ~10 instructions/frame
+const FRAME_LENGTH: time::Duration = time::Duration::new(0, 1_000_000_000 / 60); // ~60Hz
 const SPRITE_WIDTH: usize = 8;
 
+pub use asm::assemble;
+pub use audio::Tone;
+pub use disasm::disassemble;
+pub use display::Palette;
+pub use graphics::Renderer;
+pub use keypad::{Keymap, HOTKEY_HELP};
+pub use layout::MemoryLayout;
+pub use machine::MachineCode;
+pub use movie::Movie;
+pub use platform::Platform;
+pub use quirks::Quirks;
+pub use sha1::hex_digest as rom_hash;
+pub use shutdown::{install_handlers, received_signal, signal_name};
+pub use stack::MAX_DEPTH as MAX_STACK_DEPTH;
+pub use timing::Timing;
+pub use vip::{Firmware as VipFirmware, FIRMWARE_SIZE as VIP_FIRMWARE_SIZE};
+
+pub const ROM_START: usize = ROM_START_ADDR;
+pub const MAX_ROM_SIZE: usize = MEM_SIZE - ROM_START_ADDR;
+
 // 5 bytes per hex character
 const FONTS: [u8; 16 * 5] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -57,9 +98,134 @@ const OPCODE_FUNCS: [fn(&mut VM); NUM_OPCODE_TYPES] = [
     VM::nib_f,
 ];
 
+// HIRES and CHIP-8X replace a few of these with their own
+const HIRES_OPCODE_FUNCS: [fn(&mut VM); NUM_OPCODE_TYPES] = {
+    let mut funcs = OPCODE_FUNCS;
+    funcs[0x0] = VM::hires_nib_0;
+    funcs
+};
+
+const CHIP8X_OPCODE_FUNCS: [fn(&mut VM); NUM_OPCODE_TYPES] = {
+    let mut funcs = OPCODE_FUNCS;
+    funcs[0x0] = VM::chip8x_nib_0;
+    funcs[0x5] = VM::chip8x_nib_5;
+    funcs[0xB] = VM::chip8x_nib_b;
+    funcs[0xE] = VM::chip8x_nib_e;
+    funcs[0xF] = VM::chip8x_nib_f;
+    funcs
+};
+
+pub struct Options {
+    // Which variant of CHIP-8 the ROM was written for
+    pub platform: Platform,
+    // Many ROMs expect slightly different implementations for some opcodes.
+    // Quirks enforce the original CHIP-8 specification, in lieu of modern interpretations.
+    pub quirks: Quirks,
+    // Instructions per second of emulated time
+    pub cpu_hz: u32,
+    // Fixed cost per instruction, or each one's cost on the COSMAC VIP (ignores cpu_hz)
+    pub timing: Timing,
+    // Nested subroutine calls allowed before a stack overflow (default: the platform's)
+    pub stack_depth: Option<usize>,
+    // Keep the stack and screen in RAM, as on the COSMAC VIP
+    pub memory_layout: MemoryLayout,
+    // What to do with 0NNN calls to 1802 machine code
+    pub machine_code: MachineCode,
+    // Emulate a whole COSMAC VIP running its own CHIP-8 interpreter, instead of CHIP-8
+    // itself; most other CHIP-8 options then don't apply
+    pub vip_firmware: Option<VipFirmware>,
+    pub keymap: Keymap,
+    // Keys pressed by the arrow keys (up, down, left, right), for ROMs that use them
+    pub arrows: Option<[u8; 4]>,
+    // Debug mode allows stepping through the ROM instruction-by-instruction,
+    // displaying the emulator's current state (memory, registers, etc.).
+    pub debug_mode: bool,
+    // Run without a terminal for a fixed number of frames
+    pub headless: bool,
+    pub headless_frames: Option<u32>, // Default: 10 seconds, or until the movie ends
+    // Record frames to this GIF from the start
+    pub record_path: Option<PathBuf>,
+    // Text, or real pixels with the Sixel or kitty graphics protocols
+    pub renderer: Renderer,
+    // Size of each pixel in GIF recordings and graphics renderers
+    pub scale: usize,
+    pub palette: Palette,
+    // Show FPS, speed, etc. below the screen
+    pub status_bar: bool,
+    // Title or file name, for the status bar
+    pub rom_name: String,
+    // Where the ROM was loaded from, for reloading it
+    pub rom_path: Option<PathBuf>,
+    // Reload the ROM whenever the file changes, replaying the input movie if there is one
+    pub watch: bool,
+    // Fixed RNG seed; random if unset
+    pub seed: Option<u8>,
+    // Write each frame's key presses to this movie file
+    pub record_input: Option<PathBuf>,
+    // Replay key presses from a movie (overrides seed and quirks; the CLI applies its
+    // other settings),
+    // then quit or, with handoff, give control back to the user
+    pub playback: Option<Movie>,
+    pub handoff: bool,
+    // Render the buzzer to this WAV file on exit
+    pub audio_out: Option<PathBuf>,
+    pub tone: Tone,
+    pub muted: bool,
+}
+
+impl Options {
+    pub fn stack_depth(&self) -> usize {
+        self.stack_depth
+            .unwrap_or_else(|| self.platform.stack_depth())
+    }
+
+    // Room for the ROM between where the platform loads it and the end of usable RAM
+    pub fn max_rom_size(&self) -> usize {
+        self.memory_layout
+            .ram_end(self.stack_depth())
+            .saturating_sub(self.platform.rom_start())
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            platform: Platform::default(),
+            quirks: Quirks::default(),
+            cpu_hz: CPU_HZ,
+            timing: Timing::default(),
+            stack_depth: None,
+            memory_layout: MemoryLayout::default(),
+            machine_code: MachineCode::default(),
+            vip_firmware: None,
+            keymap: Keymap::default(),
+            arrows: None,
+            debug_mode: false,
+            headless: false,
+            headless_frames: None,
+            record_path: None,
+            renderer: Renderer::default(),
+            scale: 4,
+            palette: Palette::default(),
+            status_bar: false,
+            rom_name: String::new(),
+            rom_path: None,
+            watch: false,
+            seed: None,
+            record_input: None,
+            playback: None,
+            handoff: false,
+            audio_out: None,
+            tone: Tone::default(),
+            muted: false,
+        }
+    }
+}
+
 pub struct VM {
     display: display::Display,
     keypad: keypad::Keypad,
+    rom: Vec<u8>,
     mem: [u8; MEM_SIZE],
     regs: [u8; NUM_REGS],
     pc: u16,
@@ -67,103 +233,539 @@ pub struct VM {
     sound_timer: u8,
     delay_timer: u8,
     stack: stack::Stack,
+    cosmac: Option<vip::Vip>, // Set with vip_firmware
     opcode: u16,
     rng: rng::Rng,
-    beeper: Option<rodio::Sink>,
+    beeper: audio::Beeper,
     should_draw: bool,
-    legacy_mode: bool,
-    _debug_mode: bool, // Unused in release builds
+    waiting_for_vblank: bool, // Set by DXYN with the display-wait quirk, or by 0NNN
+    fault: Option<error::VmError>, // Set by an instruction that can't run; stops the VM
+    recorder: Option<gif::GifRecorder>,
+    movie_recorder: Option<movie::MovieRecorder>,
+    playback: Option<Movie>,
+    finished_movie: Option<Movie>, // Kept after handing off, to replay after a reset
+    watcher: Option<watch::FileWatcher>,
+    quirks: Quirks,
+    scheduler: scheduler::Scheduler,
+    stats: status::Stats,
+    pacer: pacer::Pacer,
+    turbo: bool,        // Run frames back to back, without waiting for 60Hz
+    fast_forward: bool, // Like turbo, while replaying a movie after a reset
+    paused: bool,
+    notice: Option<String>, // Result of the last hotkey, shown in the status bar
+    _debug_mode: bool,      // Unused in release builds
+    options: Options,
 }
 
 impl VM {
-    pub fn new(
-        rom_reader: io::BufReader<std::fs::File>,
-        legacy_mode: bool,
-        debug_mode: bool,
-    ) -> Self {
+    pub fn new(rom: Vec<u8>, mut options: Options) -> Self {
+        let headless = options.headless;
+        if let Some(arrows) = options.arrows {
+            options.keymap.set_arrows(arrows);
+        }
+
+        // Movies only replay correctly with the settings they were recorded with
+        let playback = options.playback.take();
+        if let Some(movie) = &playback {
+            options.seed = Some(movie.seed);
+            options.quirks = movie.quirks;
+        }
+
         let mut machine = VM {
-            display: display::Display::new(),
-            keypad: keypad::Keypad::new(),
+            display: if headless {
+                display::Display::headless(options.platform.display_height())
+            } else {
+                display::Display::new(
+                    options.platform.display_height(),
+                    options.renderer,
+                    options.scale,
+                    options.palette,
+                    options.status_bar,
+                )
+            },
+            keypad: if headless {
+                keypad::Keypad::headless(options.keymap)
+            } else {
+                keypad::Keypad::new(options.keymap)
+            },
+            rom,
             mem: [0; MEM_SIZE],
             regs: [0; NUM_REGS],
             index: 0,
             pc: ROM_START_ADDR as u16, // First 512 bytes reserved for internal use
             sound_timer: 0,
             delay_timer: 0,
-            stack: stack::Stack::new(),
+            stack: stack::Stack::new(options.stack_depth()),
+            cosmac: None,
             opcode: 0,
-            rng: rng::Rng::new(),
-            beeper: None, // Set this up in run()
+            rng: match options.seed {
+                Some(seed) => rng::Rng::with_seed(seed),
+                None => rng::Rng::new(),
+            },
+            beeper: if headless {
+                audio::Beeper::silent(options.tone, options.audio_out.is_some())
+            } else {
+                audio::Beeper::new(options.tone, options.audio_out.is_some())
+            },
             should_draw: false,
-            legacy_mode,
-            _debug_mode: debug_mode,
+            waiting_for_vblank: false,
+            fault: None,
+            recorder: None,
+            movie_recorder: None,
+            playback,
+            finished_movie: None,
+            watcher: match &options.rom_path {
+                Some(path) if options.watch => Some(watch::FileWatcher::new(path)),
+                _ => None,
+            },
+            quirks: options.quirks,
+            scheduler: scheduler::Scheduler::new(match options.timing {
+                Timing::Fixed => options.cpu_hz,
+                Timing::Vip => timing::VIP_CYCLES_PER_FRAME * scheduler::TIMER_HZ as u32,
+            }),
+            stats: status::Stats::new(),
+            pacer: pacer::Pacer::new(FRAME_LENGTH),
+            turbo: false,
+            fast_forward: false,
+            paused: false,
+            notice: None,
+            _debug_mode: options.debug_mode,
+            options,
         };
 
-        // Init fonts
-        machine.mem[..FONTS.len()].copy_from_slice(&FONTS);
+        machine.power_on();
 
-        // Load ROM
-        for (i, byte) in rom_reader.bytes().enumerate() {
-            machine.mem[ROM_START_ADDR + i] = byte.unwrap();
+        if machine.options.muted {
+            machine.beeper.toggle_mute();
+        }
+
+        if machine.playback.is_some() {
+            machine.keypad.set_ignore_rom_keys(true);
         }
 
         machine
     }
 
-    pub fn run(mut self) {
+    // Runs until quit, or until the ROM does something it can't go on from
+    pub fn run(&mut self) -> Result<(), error::VmError> {
         // Acquire stdout lock continuously for slight performance gain
         let _handle = io::stdout().lock();
 
-        // Used to time each frame to get ~60Hz runtime
-        const FRAME_LENGTH: time::Duration = time::Duration::new(0, 1_000_000_000 / 60);
-
-        // Do audio setup here to avoid lifetimes
-        let (_stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
-        let sink = rodio::Sink::try_new(&stream_handle).unwrap();
+        // Headless runs go as fast as possible, for a fixed number of frames
+        let headless = self.display.is_headless();
+        let mut frames_left = match self.options.headless_frames {
+            Some(frames) => frames,
+            None if self.playback.is_some() => u32::MAX,
+            None => 600,
+        };
+        if let Some(path) = &self.options.record_path {
+            self.recorder = Some(self.new_recorder(path).expect("Failed to create GIF!"));
+        }
 
-        // Tune to the "correct" A4 ;)
-        let source = rodio::source::SineWave::new(442.);
-        sink.append(source);
-        sink.pause();
-        self.beeper = Some(sink);
+        if let Some(path) = &self.options.record_input {
+            let recorder = movie::MovieRecorder::new(
+                path,
+                &rom_hash(&self.rom),
+                self.rng.seed(),
+                &self.options,
+            );
+            self.movie_recorder = Some(recorder.expect("Failed to create input movie!"));
+        }
 
+        let mut result = Ok(());
+        self.pacer.resync();
         while !self.keypad.got_sigint() {
-            let start_time = time::Instant::now();
-
-            // Read next key input, and decrement key down timers
+            // Read next key input
             self.keypad.cycle();
 
-            // One instruction per frame is sluggish
-            for _ in 0..INSTR_PER_FRAME {
-                self.print_state().expect("Failed to print state!"); // No-op in release builds
-                self.exec_instr();
+            let hotkey = self.keypad.take_hotkey();
+            if hotkey.is_some() {
+                self.notice = None;
+            }
+
+            let mut step_frame = false;
+            match hotkey {
+                Some(keypad::Hotkey::ToggleRecording) => self.toggle_recording(),
+                Some(keypad::Hotkey::ToggleMute) => self.beeper.toggle_mute(),
+                Some(keypad::Hotkey::ToggleTurbo) => self.turbo = !self.turbo,
+                Some(keypad::Hotkey::TogglePause) => self.toggle_pause(),
+                Some(keypad::Hotkey::StepFrame) => step_frame = self.paused,
+                Some(keypad::Hotkey::Reset) => self.reset(),
+                Some(keypad::Hotkey::Reload) => self.reload(),
+                None => {}
+            }
+
+            if self.watcher.as_mut().is_some_and(|watcher| watcher.poll()) {
+                self.notice = None;
+                self.reload();
             }
 
-            self.decrement_timers();
+            // Turbo mode runs as many frames as fit in one frame of real time, and
+            // still only draws at 60Hz, so the terminal can keep up
+            if !self.paused || step_frame {
+                let batch_start = time::Instant::now();
+                let mut stopped = false;
+                loop {
+                    match self.emulate_frame() {
+                        Ok(true) => {}
+                        Ok(false) => stopped = true,
+                        Err(err) => {
+                            result = Err(err);
+                            stopped = true;
+                        }
+                    }
 
-            if self.should_draw {
+                    if stopped || !self.uncapped() || batch_start.elapsed() >= FRAME_LENGTH {
+                        break;
+                    }
+                }
+
+                if stopped {
+                    break;
+                }
+            }
+
+            if self.display.poll_resize() || self.display.set_status(self.status_text()) {
+                self.should_draw = true;
+            }
+
+            let uncapped = self.uncapped();
+            if self.should_draw && (headless || uncapped || self.pacer.should_draw()) {
                 self.should_draw = false;
                 self.display.draw().expect("Failed to draw frame!");
             }
 
+            if headless {
+                frames_left -= 1;
+                if frames_left == 0 {
+                    break;
+                }
+
+                continue;
+            }
+
+            if uncapped {
+                self.pacer.resync();
+                continue;
+            }
+
             // Wait for end of frame to enforce 60Hz refresh rate
-            let end_time = time::Instant::now();
-            let wait_time = FRAME_LENGTH
-                .checked_sub(end_time.saturating_duration_since(start_time))
-                .unwrap_or(time::Duration::new(0, 0));
-            std::thread::sleep(wait_time);
+            self.pacer.wait();
+        }
+
+        if let Some(recorder) = self.recorder.take() {
+            recorder.finish().expect("Failed to finish GIF!");
+        }
+
+        if let Some(recorder) = self.movie_recorder.take() {
+            recorder.finish().expect("Failed to finish input movie!");
+        }
+
+        if let Some(path) = &self.options.audio_out {
+            self.beeper.write_wav(path).expect("Failed to write audio!");
+        }
+
+        result
+    }
+
+    // One 60Hz frame of emulated time: the frame's key presses, the instructions the CPU
+    // gets through in that time, then a timer tick. False once the input movie is over.
+    fn emulate_frame(&mut self) -> Result<bool, error::VmError> {
+        if let Some(movie) = self.playback.as_mut() {
+            match movie.next_frame() {
+                Some(key_presses) => self.keypad.press_mask(key_presses),
+                // Keep going while watching; the movie only sets the scene
+                None if self.options.handoff || self.options.watch => {
+                    self.finished_movie = self.playback.take();
+                    self.fast_forward = false;
+                    self.keypad.set_ignore_rom_keys(false);
+                }
+                None => return Ok(false),
+            }
+        }
+
+        if let Some(recorder) = self.movie_recorder.as_mut() {
+            recorder
+                .record_frame(self.keypad.frame_presses())
+                .expect("Failed to record input!");
+        }
+
+        if let Some(cosmac) = self.cosmac.as_mut() {
+            let keys = (0..16)
+                .filter(|&key| self.keypad.is_key_pressed(key))
+                .fold(0, |keys, key| keys | (1 << key));
+            let instructions = cosmac.run_frame(keys);
+            self.beeper.set_playing(cosmac.tone());
+            self.beeper.sample_frame();
+            self.stats.frame(instructions);
+            let height = self.display.height();
+            if self
+                .display
+                .update(|row, col| cosmac.pixel(row, col, height))
+            {
+                self.should_draw = true;
+            }
+        } else {
+            self.run_chip8_frame()?;
         }
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder
+                .capture(self.display.pixels())
+                .expect("Failed to record frame!");
+        }
+
+        self.keypad.end_frame();
+        Ok(true)
+    }
+
+    // The instructions the CPU gets through before the next 60Hz timer tick
+    fn run_chip8_frame(&mut self) -> Result<(), error::VmError> {
+        let mut instructions = 0;
+        while !self.scheduler.tick_due() {
+            self.print_state().expect("Failed to print state!"); // No-op in release builds
+            let pc = self.pc;
+            let vx = self.regs[((self.opcode_at(pc) & 0x0F00) >> 8) as usize]; // Before it changes
+            self.exec_instr()?;
+            self.scheduler.run_cycles(match self.options.timing {
+                Timing::Fixed => 1,
+                Timing::Vip => timing::vip_cycles(self.opcode, vx, self.pc == pc + 4),
+            });
+
+            if std::mem::take(&mut self.waiting_for_vblank) {
+                self.scheduler.skip_to_tick();
+            }
+            instructions += 1;
+        }
+
+        self.scheduler.tick();
+        self.decrement_timers();
+        self.beeper.sample_frame();
+        self.stats.frame(instructions);
+        self.sync_display();
+        Ok(())
     }
 
-    fn exec_instr(&mut self) {
-        // Opcodes are 2 bytes long
+    // Frames run back to back, without waiting for 60Hz
+    fn uncapped(&self) -> bool {
+        (self.turbo || self.fast_forward) && !self.paused && !self.display.is_headless()
+    }
+
+    // Memory, registers, timers and screen as they are when the machine is switched on
+    fn power_on(&mut self) {
+        self.mem = [0; MEM_SIZE];
+        self.regs = [0; NUM_REGS];
+        self.index = 0;
+        self.pc = self.options.platform.start_addr() as u16;
+        self.sound_timer = 0;
+        self.delay_timer = 0;
+        self.stack = match self.options.memory_layout {
+            MemoryLayout::Separate => stack::Stack::new(self.options.stack_depth()),
+            MemoryLayout::Vip => stack::Stack::in_ram(self.options.stack_depth()),
+        };
+        self.opcode = 0;
+        self.beeper.reset();
+        self.scheduler.reset();
+        self.display
+            .frame_buffer
+            .fill([display::OFF_PIXEL; DISPLAY_WIDTH]);
+        self.display.colors =
+            (self.options.platform == Platform::Chip8X).then(platform::ColorBoard::new);
+        self.should_draw = true;
+
+        // Init fonts
+        self.mem[..FONTS.len()].copy_from_slice(&FONTS);
+
+        // Load ROM
+        let rom_start = self.options.platform.rom_start();
+        self.mem[rom_start..rom_start + self.rom.len()].copy_from_slice(&self.rom);
+
+        if let Some(firmware) = &self.options.vip_firmware {
+            self.cosmac = Some(vip::Vip::new(firmware, &self.rom, rom_start));
+        }
+    }
+
+    // Pausing silences the buzzer until the ROM runs again
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.beeper
+            .set_playing(!self.paused && self.sound_timer > 0);
+    }
+
+    // Input movies only record key presses, so they can't record a reset
+    fn recording_movie(&mut self) -> bool {
+        let recording = self.movie_recorder.is_some();
+        if recording {
+            self.notice = Some(String::from("can't reset while recording an input movie"));
+        }
+
+        recording
+    }
+
+    // Switch the machine off and on again. If an input movie was played, it's replayed
+    // from the start as fast as possible, to get back to where it left off.
+    fn restart(&mut self) {
+        self.power_on();
+        self.keypad.release_all();
+        if let Some(mut movie) = self.playback.take().or(self.finished_movie.take()) {
+            movie.rewind();
+            self.rng = rng::Rng::with_seed(movie.seed);
+            self.keypad.set_ignore_rom_keys(true);
+            self.playback = Some(movie);
+            self.fast_forward = true;
+        }
+    }
+
+    // Ctrl-R: restart the ROM
+    fn reset(&mut self) {
+        if self.recording_movie() {
+            return;
+        }
+
+        self.restart();
+        self.notice = Some(String::from("reset"));
+    }
+
+    // Ctrl-L (or a change with --watch): load the ROM from disk again (e.g. after rebuilding
+    // it), then restart. If it can't be read, the current ROM keeps running.
+    fn reload(&mut self) {
+        if self.recording_movie() {
+            return;
+        }
+
+        let path = match &self.options.rom_path {
+            Some(path) => path,
+            None => return,
+        };
+
+        match std::fs::read(path) {
+            Ok(rom) if rom.len() <= self.options.max_rom_size() => {
+                self.rom = rom;
+                self.restart();
+                self.notice = Some(String::from("reloaded"));
+            }
+            Ok(_) => self.notice = Some(String::from("reload failed: ROM too big")),
+            Err(err) => self.notice = Some(format!("reload failed: {}", err)),
+        }
+    }
+
+    // How well frames kept to the 60Hz schedule, if the status bar was on
+    pub fn pacing_summary(&self) -> Option<String> {
+        let shown = self.options.status_bar && !self.display.is_headless();
+        shown.then(|| format!("Frame pacing: {}", self.pacer.stats.summary()))
+    }
+
+    fn status_text(&self) -> String {
+        status::Status {
+            rom_name: &self.options.rom_name,
+            stats: &self.stats,
+            pacing: &self.pacer.stats,
+            cpu_hz: match self.options.timing {
+                Timing::Fixed if self.cosmac.is_none() => Some(self.scheduler.cpu_hz()),
+                _ => None,
+            },
+            quirks: match self.cosmac {
+                Some(_) => String::from("COSMAC VIP"),
+                None => self.quirks.profile(),
+            },
+            turbo: self.turbo,
+            paused: self.paused,
+            notice: self.notice.as_deref(),
+            sound: match &self.cosmac {
+                Some(cosmac) => cosmac.tone(),
+                None => self.sound_timer > 0,
+            },
+            muted: self.beeper.is_muted(),
+            recording: self.recorder.is_some(),
+        }
+        .text()
+    }
+
+    fn new_recorder(&self, path: &std::path::Path) -> Result<gif::GifRecorder, io::Error> {
+        gif::GifRecorder::new(
+            path,
+            DISPLAY_WIDTH,
+            self.display.height(),
+            self.options.scale,
+            self.options.palette,
+        )
+    }
+
+    // Ctrl-G starts recording to a timestamped GIF in the working directory,
+    // and pressing it again finishes the file
+    fn toggle_recording(&mut self) {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish().expect("Failed to finish GIF!"),
+            None => {
+                let timestamp = time::SystemTime::now()
+                    .duration_since(time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                let path = PathBuf::from(format!("chip8-{}.gif", timestamp));
+                self.recorder = Some(self.new_recorder(&path).expect("Failed to create GIF!"));
+            }
+        }
+    }
+
+    // Current screen as text, one line per row ('#' = on, '.' = off)
+    pub fn screen_text(&self) -> String {
+        let mut text = String::new();
+        for row in self.display.pixels().chunks(DISPLAY_WIDTH) {
+            text.extend(row.iter().map(|&on| if on { '#' } else { '.' }));
+            text.push('\n');
+        }
+
+        text
+    }
+
+    // SHA-1 of the screen text, for checking a ROM's output in automated tests
+    pub fn screen_hash(&self) -> String {
+        sha1::hex_digest(self.screen_text().as_bytes())
+    }
+
+    // Registers, timers and stack, for error reports
+    pub fn state_summary(&self) -> String {
+        if let Some(cosmac) = &self.cosmac {
+            return cosmac.state_summary();
+        }
+
+        format!(
+            "Opcode: 0x{:04X}, PC: 0x{:03X}, Index register: 0x{:03X}\n\
+            Delay timer: 0x{:X}, Sound timer: 0x{:X}\n\
+            Registers: {:X?}\n\
+            Stack ({} of {}): {:X?}",
+            self.opcode,
+            self.pc,
+            self.index,
+            self.delay_timer,
+            self.sound_timer,
+            self.regs,
+            self.stack.entries(&self.mem).len(),
+            self.stack.depth(),
+            self.stack.entries(&self.mem)
+        )
+    }
+
+    // Opcodes are 2 bytes long
+    fn opcode_at(&self, addr: u16) -> u16 {
+        let addr = addr as usize;
+        ((self.mem[addr] as u16) << 8) | (self.mem[addr + 1] as u16)
+    }
+
+    fn exec_instr(&mut self) -> Result<(), error::VmError> {
         let pc = self.pc as usize;
-        self.opcode = ((self.mem[pc] as u16) << 8) | (self.mem[pc + 1] as u16);
+        self.opcode = self.opcode_at(self.pc);
         let op_type = ((self.mem[pc] & 0xF0) >> 4) as usize;
         self.pc += 2;
 
         debug_assert!(op_type <= NUM_OPCODE_TYPES, "Unknown opcode!");
-        OPCODE_FUNCS[op_type](self);
+        let funcs = match self.options.platform {
+            Platform::Chip8 => &OPCODE_FUNCS,
+            Platform::Hires => &HIRES_OPCODE_FUNCS,
+            Platform::Chip8X => &CHIP8X_OPCODE_FUNCS,
+        };
+        funcs[op_type](self);
+        self.fault.take().map_or(Ok(()), Err)
     }
 
     fn decrement_timers(&mut self) {
@@ -176,40 +778,77 @@ impl VM {
 
             // Beep while sound timer is nonzero
             if self.sound_timer == 0 {
-                self.beeper.as_ref().unwrap().pause();
+                self.beeper.set_playing(false);
             }
         }
     }
 
     // 00E0: clear display
     // 00EE: return from subroutine
-    // (Not supporting machine code routines with 0NNN)
+    // 0NNN: run machine code routine at NNN (see machine_code)
     fn nib_0(&mut self) {
         match self.opcode {
-            0x00E0 => {
-                self.display
-                    .frame_buffer
-                    .fill([display::OFF_PIXEL; DISPLAY_WIDTH]);
+            0x00E0 => self.clear_screen(),
+            0x00EE => match self.stack.pop(&self.mem) {
+                Ok(addr) => self.pc = addr,
+                Err(err) => self.fault = Some(self.stack_error(err)),
+            },
+            _ => self.machine_code(self.opcode & 0x0FFF),
+        }
+    }
+
+    fn machine_code(&mut self, addr: u16) {
+        let routine = match self.options.machine_code {
+            MachineCode::Ignore => return,
+            MachineCode::Error => None,
+            MachineCode::Emulate => machine::recognize(&self.mem, addr),
+        };
+
+        match routine {
+            Some(machine::Routine::Return) => {}
+            Some(machine::Routine::ClearScreen) => self.clear_screen(),
+            Some(machine::Routine::WaitForInterrupt) => self.waiting_for_vblank = true,
+            None => {
+                self.fault = Some(error::VmError::MachineCode {
+                    opcode: self.opcode,
+                    addr,
+                })
             }
-            0x00EE => self.pc = self.stack.pop(),
-            _ => panic!("{}: Unsupported opcode!", self.opcode),
+        }
+    }
+
+    // Jumps below the ROM or past the end of memory stop the VM
+    fn jump(&mut self, target: u16) {
+        let opcode = self.opcode;
+        if (target as usize) < self.options.platform.rom_start() {
+            self.fault = Some(error::VmError::JumpIntoInterpreter { opcode, target });
+        } else if (target as usize) >= MEM_SIZE - 1 {
+            self.fault = Some(error::VmError::JumpPastMemory { opcode, target });
+        } else {
+            self.pc = target;
         }
     }
 
     // 1NNN: goto address NNN
     fn nib_1(&mut self) {
-        let addr = self.opcode & 0x0FFF;
-        debug_assert!(
-            (addr as usize) >= ROM_START_ADDR && (addr as usize) < MEM_SIZE,
-            "Invalid address!",
-        );
-        self.pc = addr;
+        self.jump(self.opcode & 0x0FFF);
     }
 
     // 2NNN: call subroutine at address NNN
     fn nib_2(&mut self) {
-        self.stack.push(self.pc);
-        self.nib_1();
+        match self.stack.push(self.pc, &mut self.mem) {
+            Ok(()) => self.nib_1(),
+            Err(err) => self.fault = Some(self.stack_error(err)),
+        }
+    }
+
+    fn stack_error(&self, error: stack::StackError) -> error::VmError {
+        error::VmError::Stack {
+            opcode: self.opcode,
+            error,
+            depth: self.stack.depth(),
+            calls: self.stack.call_chain(&self.mem),
+        }
     }
 
     // 3XNN: if (Vx != NN) skip next instruction
@@ -313,7 +952,7 @@ impl VM {
             // 8XY6
             |vm, reg_x, reg_y| {
                 // In original spec, set Vx = Vy first
-                if vm.legacy_mode {
+                if vm.quirks.shift {
                     vm.regs[reg_x] = vm.regs[reg_y];
                 }
 
@@ -333,7 +972,7 @@ impl VM {
             // 8XYE
             |vm, reg_x, reg_y| {
                 // In original spec, set Vx = Vy first
-                if vm.legacy_mode {
+                if vm.quirks.shift {
                     vm.regs[reg_x] = vm.regs[reg_y];
                 }
 
@@ -371,13 +1010,15 @@ impl VM {
     }
 
     // BNNN: PC = V0 + NNN
+    // BXNN: PC = Vx + XNN (jump quirk)
     fn nib_b(&mut self) {
-        let target = (self.opcode & 0x0FFF) + (self.regs[0] as u16);
-        debug_assert!(
-            (target as usize) >= ROM_START_ADDR && (target as usize) < MEM_SIZE,
-            "Invalid address!",
-        );
-        self.pc = target;
+        let reg_num = if self.quirks.jump {
+            ((self.opcode & 0x0F00) >> 8) as usize
+        } else {
+            0
+        };
+
+        self.jump((self.opcode & 0x0FFF) + (self.regs[reg_num] as u16));
     }
 
     // CXNN: Vx = rand[0, 255] & NN
@@ -398,35 +1039,101 @@ impl VM {
         debug_assert!(reg_x < NUM_REGS, "Invalid register X!");
         debug_assert!(reg_y < NUM_REGS, "Invalid register Y!");
 
+        let height = self.display.height();
         let x_coord = (self.regs[reg_x] as usize) % DISPLAY_WIDTH;
-        let y_coord = (self.regs[reg_y] as usize) % DISPLAY_HEIGHT;
+        let y_coord = (self.regs[reg_y] as usize) % height;
         let sprite_addr = self.index as usize;
 
         // Vf = 1 if redraw turns off at least one pixel; init to 0
         self.regs[0xF] = 0;
 
-        // DISPLAY_HEIGHT.min(y_coord + num_rows): don't write beyond bottom edge of display
-        for (row_offset, row) in (y_coord..DISPLAY_HEIGHT.min(y_coord + num_rows)).enumerate() {
+        // Without the wrap quirk, don't write beyond bottom or right edge of display
+        let (num_rows, num_cols) = if self.quirks.wrap {
+            (num_rows, SPRITE_WIDTH)
+        } else {
+            (
+                num_rows.min(height - y_coord),
+                SPRITE_WIDTH.min(DISPLAY_WIDTH - x_coord),
+            )
+        };
+
+        for row_offset in 0..num_rows {
+            let row = (y_coord + row_offset) % height;
             let mut sprite_row = self.mem[sprite_addr + row_offset];
 
-            // don't write beyond right edge of display
-            for col in x_coord..DISPLAY_WIDTH.min(x_coord + SPRITE_WIDTH) {
+            for col_offset in 0..num_cols {
+                let col = (x_coord + col_offset) % DISPLAY_WIDTH;
                 let pixel_state = sprite_row >> (SPRITE_WIDTH - 1);
                 sprite_row <<= 1;
 
                 if pixel_state == 1 {
-                    if self.display.frame_buffer[row][col] == display::ON_PIXEL {
-                        self.display.frame_buffer[row][col] = display::OFF_PIXEL;
+                    if self.pixel(row, col) {
+                        self.set_pixel(row, col, false);
                         // Vf = 1 if redraw turns off a pixel
                         self.regs[0xF] = 1;
                     } else {
-                        self.display.frame_buffer[row][col] = display::ON_PIXEL;
+                        self.set_pixel(row, col, true);
                     }
                 }
             }
         }
 
         self.should_draw = true;
+        self.waiting_for_vblank = self.quirks.display_wait;
+    }
+
+    // With the VIP memory layout, the screen is in RAM and the display only shows it
+    fn pixel(&self, row: usize, col: usize) -> bool {
+        match self.options.memory_layout {
+            MemoryLayout::Separate => self.display.frame_buffer[row][col] == display::ON_PIXEL,
+            MemoryLayout::Vip => layout::vip_pixel(&self.mem, row, col),
+        }
+    }
+
+    fn set_pixel(&mut self, row: usize, col: usize, on: bool) {
+        match self.options.memory_layout {
+            MemoryLayout::Separate => {
+                self.display.frame_buffer[row][col] = if on {
+                    display::ON_PIXEL
+                } else {
+                    display::OFF_PIXEL
+                };
+            }
+            MemoryLayout::Vip => {
+                let addr = layout::VIP_DISPLAY_ADDR + row * DISPLAY_WIDTH / 8 + col / 8;
+                let bit = 0x80 >> (col % 8);
+                if on {
+                    self.mem[addr] |= bit;
+                } else {
+                    self.mem[addr] &= !bit;
+                }
+            }
+        }
+    }
+
+    fn clear_screen(&mut self) {
+        match self.options.memory_layout {
+            MemoryLayout::Separate => self
+                .display
+                .frame_buffer
+                .fill([display::OFF_PIXEL; DISPLAY_WIDTH]),
+            MemoryLayout::Vip => self.mem[layout::VIP_DISPLAY_ADDR..].fill(0),
+        }
+    }
+
+    // Show the screen's RAM, which the ROM may also have written to directly
+    fn sync_display(&mut self) {
+        if self.options.memory_layout != MemoryLayout::Vip {
+            return;
+        }
+
+        let mem = &self.mem;
+        if self
+            .display
+            .update(|row, col| layout::vip_pixel(mem, row, col))
+        {
+            self.should_draw = true;
+        }
     }
 
     // EX9E: if (key() == Vx) skip next instruction
@@ -453,6 +1160,17 @@ impl VM {
         debug_assert!(reg_num < NUM_REGS, "Invalid register!");
 
         match op_type {
+            // F002: load XO-CHIP audio pattern from mem[I..I+16]
+            0x02 if reg_num == 0 => {
+                let index = self.index as usize;
+                debug_assert!(
+                    (index + audio::PATTERN_LEN) <= MEM_SIZE,
+                    "Index register reading out-of-bounds memory!",
+                );
+                let mut pattern = [0; audio::PATTERN_LEN];
+                pattern.copy_from_slice(&self.mem[index..index + audio::PATTERN_LEN]);
+                self.beeper.load_pattern(pattern);
+            }
             // FX07: Vx = delay timer
             0x07 => self.regs[reg_num] = self.delay_timer,
             // FX0A: Vx = next key down; blocks for user input
@@ -469,7 +1187,7 @@ impl VM {
             0x18 => {
                 self.sound_timer = self.regs[reg_num];
                 if self.sound_timer > 0 {
-                    self.beeper.as_ref().unwrap().play();
+                    self.beeper.set_playing(true);
                 }
             }
             // FX1E: I += Vx
@@ -492,6 +1210,8 @@ impl VM {
                 self.mem[index + 1] = (reg_val / 10) % 10; // Tenths place
                 self.mem[index + 2] = reg_val % 10; // Ones place
             }
+            // FX3A: XO-CHIP audio pattern pitch = Vx
+            0x3A => self.beeper.set_pitch(self.regs[reg_num]),
             // FX55: store regs V0 to Vx in memory
             0x55 => {
                 let index = self.index as usize;
@@ -502,7 +1222,7 @@ impl VM {
                 self.mem[index..index + reg_num + 1].copy_from_slice(&self.regs[..reg_num + 1]);
 
                 // Index is incremented only in original CHIP-8 spec
-                if self.legacy_mode {
+                if self.quirks.load_store {
                     self.index += (reg_num as u16) + 1;
                 }
             }
@@ -516,7 +1236,7 @@ impl VM {
                 self.regs[..reg_num + 1].copy_from_slice(&self.mem[index..index + reg_num + 1]);
 
                 // Index is incremented only in original CHIP-8 spec
-                if self.legacy_mode {
+                if self.quirks.load_store {
                     self.index += (reg_num as u16) + 1;
                 }
             }
@@ -524,6 +1244,93 @@ impl VM {
         }
     }
 
+    // HIRES
+    // 0230: clear display
+    fn hires_nib_0(&mut self) {
+        match self.opcode {
+            0x0230 => self.clear_screen(),
+            _ => self.nib_0(),
+        }
+    }
+
+    // CHIP-8X
+    // 02A0: step the background color through blue, black, green and red
+    fn chip8x_nib_0(&mut self) {
+        match self.opcode {
+            0x02A0 => {
+                self.color_board().next_background();
+                self.should_draw = true;
+            }
+            _ => self.nib_0(),
+        }
+    }
+
+    // 5XY1: Vx += Vy, adding each nibble separately and keeping its low 3 bits
+    fn chip8x_nib_5(&mut self) {
+        if self.opcode & 0x000F != 0x1 {
+            return self.nib_5();
+        }
+
+        let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
+        let reg_y = ((self.opcode & 0x00F0) >> 4) as usize;
+        self.regs[reg_x] = ((self.regs[reg_x] & 0x77) + (self.regs[reg_y] & 0x77)) & 0x77;
+    }
+
+    // BXY0: set the foreground color of zones of 8x4 pixels to Vy
+    // BXYN: the same for strips of 8x1 pixels, N rows of them
+    // Vx's low nibble is the first column of 8 pixels, and its high nibble how many more to
+    // color. V(x+1) is the same for rows of zones, or for BXYN, the first row of pixels.
+    fn chip8x_nib_b(&mut self) {
+        let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
+        let reg_y = ((self.opcode & 0x00F0) >> 4) as usize;
+        let num_rows = (self.opcode & 0x000F) as usize;
+        let horizontal = self.regs[reg_x] as usize;
+        let vertical = self.regs[(reg_x + 1) % NUM_REGS] as usize;
+
+        let first_col = horizontal & 0x0F;
+        let cols = first_col..=first_col + (horizontal >> 4);
+        let rows = if num_rows == 0 {
+            let first_zone = vertical & 0x0F;
+            let last_zone = first_zone + (vertical >> 4);
+            first_zone * platform::ZONE_HEIGHT..=(last_zone + 1) * platform::ZONE_HEIGHT - 1
+        } else {
+            vertical..=vertical + num_rows - 1
+        };
+
+        let color = self.regs[reg_y];
+        self.color_board().set_foreground(cols, rows, color);
+        self.should_draw = true;
+    }
+
+    // EXF2: if (keypad 2's key() == Vx) skip next instruction
+    // EXF5: if (keypad 2's key() != Vx) skip next instruction
+    // Only one keypad is mapped to the keyboard, so none of keypad 2's keys are ever down
+    fn chip8x_nib_e(&mut self) {
+        match self.opcode & 0x00FF {
+            0xF2 => {}
+            0xF5 => self.pc += 2,
+            _ => self.nib_e(),
+        }
+    }
+
+    // FXF8: send Vx to the VP-595 sound board, as the tone's pitch. The board isn't
+    // emulated, so the buzzer keeps its own tone.
+    // FXFB: wait for input on I/O port 2 and put it in Vx. Nothing is ever plugged in, so
+    // waiting would hang; carry on with Vx as it was.
+    fn chip8x_nib_f(&mut self) {
+        match self.opcode & 0x00FF {
+            0xF8 | 0xFB => {}
+            _ => self.nib_f(),
+        }
+    }
+
+    fn color_board(&mut self) -> &mut platform::ColorBoard {
+        self.display
+            .colors
+            .as_mut()
+            .expect("CHIP-8X needs the color board!")
+    }
+
     #[cfg(debug_assertions)]
     fn print_state(&mut self) -> Result<(), std::io::Error> {
         if self._debug_mode {
@@ -544,14 +1351,14 @@ impl VM {
                 self.opcode, self.pc, self.index
             )?;
 
-            if (self.index as usize) < FONTS.len() && self.index % 5 == 0 {
+            if (self.index as usize) < FONTS.len() && self.index.is_multiple_of(5) {
                 write!(
                     output,
                     "(Index register pointing to sprite {:X})\r\n",
                     self.index / 5
                 )?;
             } else {
-                write!(output, "\n")?;
+                writeln!(output)?;
             }
 
             write!(
@@ -561,7 +1368,7 @@ impl VM {
             )?;
 
             write!(output, "Registers: {:X?}\r\n", self.regs)?;
-            write!(output, "Stack: {:X?}\r\n", self.stack)?;
+            write!(output, "Stack: {:X?}\r\n", self.stack.entries(&self.mem))?;
 
             if (self.pc as usize) < MEM_SIZE {
                 let upper_bound = MEM_SIZE.min((self.pc + 16) as usize);
@@ -578,7 +1385,7 @@ impl VM {
             write!(output, "Press 's' to step or 'c' to continue\r\n")?;
             output.flush()?;
 
-            loop {
+            while !self.keypad.got_sigint() {
                 if let Some(Ok(key)) = self.keypad.read_stdin() {
                     match key {
                         Key::Char('s') => break,
@@ -605,3 +1412,477 @@ impl VM {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Headless, with a fixed seed
+    fn vm_with(rom: &[u8], options: Options) -> VM {
+        VM::new(
+            rom.to_vec(),
+            Options {
+                headless: true,
+                seed: Some(0),
+                ..options
+            },
+        )
+    }
+
+    fn run(vm: &mut VM, instructions: usize) -> Result<(), error::VmError> {
+        for _ in 0..instructions {
+            vm.exec_instr()?;
+        }
+
+        Ok(())
+    }
+
+    fn chip8x(rom: &[u8]) -> VM {
+        vm_with(
+            rom,
+            Options {
+                platform: Platform::Chip8X,
+                ..Default::default()
+            },
+        )
+    }
+
+    const RED: [u8; 3] = [0xFF, 0x00, 0x00];
+    const BLUE: [u8; 3] = [0x00, 0x00, 0xFF];
+    const GREEN: [u8; 3] = [0x00, 0xFF, 0x00];
+
+    #[test]
+    fn chip8x_adds_nibbles_separately() {
+        // V0 = 37, V1 = 45, 5011, then 5010 doesn't skip since V0 != V1
+        let mut vm = chip8x(&[0x60, 0x37, 0x61, 0x45, 0x50, 0x11, 0x50, 0x10]);
+        assert_eq!(vm.pc, 0x300);
+        run(&mut vm, 4).unwrap();
+        assert_eq!(vm.regs[0], 0x74);
+        assert_eq!(vm.pc, 0x308);
+    }
+
+    #[test]
+    fn chip8x_colors_zones() {
+        // Columns 2-3 of 8 pixels, zone 1 (rows 4-7), blue
+        let mut vm = chip8x(&[0x60, 0x12, 0x61, 0x01, 0x62, 0x02, 0xB0, 0x20]);
+        run(&mut vm, 4).unwrap();
+
+        let colors = vm.display.colors.unwrap();
+        for (row, col, color) in [
+            (4, 16, BLUE),
+            (7, 31, BLUE),
+            (3, 16, RED),
+            (8, 16, RED),
+            (4, 15, RED),
+            (4, 32, RED),
+        ] {
+            assert_eq!(colors.color(row, col, true), color, "({}, {})", row, col);
+        }
+    }
+
+    #[test]
+    fn chip8x_colors_strips() {
+        // Column 0 of 8 pixels, rows 10-12, green
+        let mut vm = chip8x(&[0x60, 0x00, 0x61, 0x0A, 0x62, 0x04, 0xB0, 0x23]);
+        run(&mut vm, 4).unwrap();
+
+        let colors = vm.display.colors.unwrap();
+        for (row, col, color) in [
+            (10, 0, GREEN),
+            (12, 7, GREEN),
+            (9, 0, RED),
+            (13, 0, RED),
+            (10, 8, RED),
+        ] {
+            assert_eq!(colors.color(row, col, true), color, "({}, {})", row, col);
+        }
+    }
+
+    #[test]
+    fn chip8x_steps_the_background() {
+        let mut vm = chip8x(&[0x02, 0xA0, 0x02, 0xA0, 0x02, 0xA0, 0x02, 0xA0]);
+        let mut backgrounds = vec![vm.display.colors.unwrap().color(0, 0, false)];
+        for _ in 0..4 {
+            run(&mut vm, 1).unwrap();
+            backgrounds.push(vm.display.colors.unwrap().color(0, 0, false));
+        }
+
+        assert_eq!(
+            backgrounds,
+            [
+                [0x00, 0x00, 0x80],
+                [0x00, 0x00, 0x00],
+                [0x00, 0x80, 0x00],
+                [0x80, 0x00, 0x00],
+                [0x00, 0x00, 0x80],
+            ]
+        );
+    }
+
+    #[test]
+    fn chip8x_ignores_io() {
+        // V0 = 42, FXF8 (sound board), FXFB (input port)
+        let mut vm = chip8x(&[0x60, 0x2A, 0xF0, 0xF8, 0xF0, 0xFB]);
+        run(&mut vm, 3).unwrap();
+        assert_eq!(vm.regs[0], 0x2A);
+        assert_eq!(vm.pc, 0x306);
+    }
+
+    #[test]
+    fn hires_starts_after_its_1802_code() {
+        let mut rom = vec![0x12, 0x60];
+        rom.resize(0xC0, 0);
+        rom.extend([0x02, 0x30]); // 0230: clear the 64x64 screen
+
+        let mut vm = vm_with(
+            &rom,
+            Options {
+                platform: Platform::Hires,
+                ..Default::default()
+            },
+        );
+        assert_eq!(vm.pc, 0x2C0);
+        assert_eq!(vm.display.height(), 64);
+
+        vm.set_pixel(63, 5, true);
+        run(&mut vm, 1).unwrap();
+        assert!(!vm.pixel(63, 5));
+        assert_eq!(vm.pc, 0x2C2);
+    }
+
+    // Pixels that are on, as (row, col)
+    fn lit_pixels(vm: &VM) -> Vec<(usize, usize)> {
+        (0..vm.display.height())
+            .flat_map(|row| (0..DISPLAY_WIDTH).map(move |col| (row, col)))
+            .filter(|&(row, col)| vm.pixel(row, col))
+            .collect()
+    }
+
+    // I = 20A, V0 = 62, V1 = 30, then draw an 8x4 box there twice
+    const BOX_AT_62_30: [u8; 14] = [
+        0xA2, 0x0A, 0x60, 0x3E, 0x61, 0x1E, 0xD0, 0x14, 0xD0, 0x14, 0xFF, 0x81, 0x81, 0xFF,
+    ];
+
+    fn draw_box(wrap: bool) -> VM {
+        let quirks = Quirks {
+            wrap,
+            ..Default::default()
+        };
+
+        let mut vm = vm_with(
+            &BOX_AT_62_30,
+            Options {
+                quirks,
+                ..Default::default()
+            },
+        );
+        vm.set_pixel(0, 62, true); // Only in the way once the sprite wraps
+        vm
+    }
+
+    #[test]
+    fn sprites_clip_at_the_edges() {
+        let mut vm = draw_box(false);
+        run(&mut vm, 4).unwrap();
+        assert_eq!(lit_pixels(&vm), [(0, 62), (30, 62), (30, 63), (31, 62)]);
+        assert_eq!(vm.regs[0xF], 0);
+
+        run(&mut vm, 1).unwrap();
+        assert_eq!(lit_pixels(&vm), [(0, 62)]);
+        assert_eq!(vm.regs[0xF], 1);
+    }
+
+    #[test]
+    fn sprites_wrap_with_the_quirk() {
+        let mut vm = draw_box(true);
+        run(&mut vm, 4).unwrap();
+
+        // Columns 62-63 then 0-5, rows 30-31 then 0-1; (0, 62) was on, so it goes off
+        #[rustfmt::skip]
+        let expected = [
+            (0, 5),
+            (1, 0), (1, 1), (1, 2), (1, 3), (1, 4), (1, 5), (1, 62), (1, 63),
+            (30, 0), (30, 1), (30, 2), (30, 3), (30, 4), (30, 5), (30, 62), (30, 63),
+            (31, 5), (31, 62),
+        ];
+        assert_eq!(lit_pixels(&vm), expected);
+        assert_eq!(vm.regs[0xF], 1); // The sprite turned (0, 62) off
+
+        run(&mut vm, 1).unwrap();
+        assert_eq!(lit_pixels(&vm), [(0, 62)]);
+        assert_eq!(vm.regs[0xF], 1);
+    }
+
+    fn jump_vm(rom: &[u8], jump: bool) -> VM {
+        let quirks = Quirks {
+            jump,
+            ..Default::default()
+        };
+
+        vm_with(
+            rom,
+            Options {
+                quirks,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn jumps_below_the_rom_fault() {
+        let mut vm = jump_vm(&[0x11, 0xFE], false);
+        let fault = error::VmError::JumpIntoInterpreter {
+            opcode: 0x11FE,
+            target: 0x1FE,
+        };
+        assert_eq!(run(&mut vm, 1), Err(fault));
+    }
+
+    #[test]
+    fn jumps_past_memory_fault() {
+        let mut vm = jump_vm(&[0x1F, 0xFF], false);
+        let fault = error::VmError::JumpPastMemory {
+            opcode: 0x1FFF,
+            target: 0xFFF,
+        };
+        assert_eq!(run(&mut vm, 1), Err(fault));
+
+        // V0 = FF, BFFF lands beyond 12 bits
+        let mut vm = jump_vm(&[0x60, 0xFF, 0xBF, 0xFF], false);
+        let fault = error::VmError::JumpPastMemory {
+            opcode: 0xBFFF,
+            target: 0x10FE,
+        };
+        assert_eq!(run(&mut vm, 2), Err(fault));
+    }
+
+    #[test]
+    fn jumps_with_an_offset() {
+        // V0 = 2, V3 = 8, then B310
+        let rom = [0x60, 0x02, 0x63, 0x08, 0xB3, 0x10];
+
+        let mut vm = jump_vm(&rom, false);
+        run(&mut vm, 3).unwrap();
+        assert_eq!(vm.pc, 0x312); // NNN + V0
+
+        let mut vm = jump_vm(&rom, true);
+        run(&mut vm, 3).unwrap();
+        assert_eq!(vm.pc, 0x318); // XNN + V3
+    }
+
+    fn quirks_vm(rom: &[u8], quirks: &str) -> VM {
+        vm_with(
+            rom,
+            Options {
+                quirks: Quirks::parse(quirks).unwrap(),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn shifts_vy_with_the_quirk() {
+        // V0 = 10, V1 = 81, then 8016 and 801E
+        for (quirks, right, left) in [
+            ("none", (0x08, 0), (0x20, 0)),
+            ("shift", (0x40, 1), (0x02, 1)),
+        ] {
+            let mut vm = quirks_vm(&[0x60, 0x10, 0x61, 0x81, 0x80, 0x16], quirks);
+            run(&mut vm, 3).unwrap();
+            assert_eq!((vm.regs[0], vm.regs[0xF]), right, "8XY6 {}", quirks);
+
+            let mut vm = quirks_vm(&[0x60, 0x10, 0x61, 0x81, 0x80, 0x1E], quirks);
+            run(&mut vm, 3).unwrap();
+            assert_eq!((vm.regs[0], vm.regs[0xF]), left, "8XYE {}", quirks);
+        }
+    }
+
+    #[test]
+    fn loads_and_stores_move_i_with_the_quirk() {
+        // I = 300, V0 = 1, V1 = 2, F155, then clear V0-V1 and load them back with F165
+        let rom = [
+            0xA3, 0x00, 0x60, 0x01, 0x61, 0x02, 0xF1, 0x55, 0x60, 0x00, 0x61, 0x00, 0xA3, 0x00,
+            0xF1, 0x65,
+        ];
+        for (quirks, index) in [("none", 0x300), ("load-store", 0x302)] {
+            let mut vm = quirks_vm(&rom, quirks);
+            run(&mut vm, 4).unwrap();
+            assert_eq!(vm.mem[0x300..0x302], [1, 2]);
+            assert_eq!(vm.index, index, "FX55 {}", quirks);
+
+            run(&mut vm, 4).unwrap();
+            assert_eq!(vm.regs[..2], [1, 2]);
+            assert_eq!(vm.index, index, "FX65 {}", quirks);
+        }
+    }
+
+    #[test]
+    fn display_wait_ends_the_frame() {
+        // Loop: V0 += 1, draw, so V0 counts the draws in a frame
+        let rom = [0x70, 0x01, 0xD0, 0x11, 0x12, 0x00];
+
+        let mut vm = quirks_vm(&rom, "none");
+        vm.run_chip8_frame().unwrap();
+        assert_eq!(vm.regs[0], 4); // 10 instructions
+
+        for quirks in ["display-wait", "vip"] {
+            let mut vm = quirks_vm(&rom, quirks);
+            vm.run_chip8_frame().unwrap();
+            assert_eq!(vm.regs[0], 1, "{}", quirks);
+            vm.run_chip8_frame().unwrap();
+            assert_eq!(vm.regs[0], 2, "{}", quirks);
+        }
+    }
+
+    fn vip_layout_vm(rom: &[u8], stack_depth: usize) -> VM {
+        vm_with(
+            rom,
+            Options {
+                memory_layout: MemoryLayout::Vip,
+                stack_depth: Some(stack_depth),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn vip_layout_draws_into_ram() {
+        // I = 20A, V0 = 9, V1 = 2, D011, then 00E0
+        let rom = [
+            0xA2, 0x0A, 0x60, 0x09, 0x61, 0x02, 0xD0, 0x11, 0x00, 0xE0, 0xFF,
+        ];
+        let mut vm = vip_layout_vm(&rom, 12);
+        run(&mut vm, 4).unwrap();
+
+        // Row 2 starts at F10; columns 9-16 are the low 7 bits of F11 and the high bit of F12
+        assert_eq!(vm.mem[0xF10..0xF14], [0x00, 0x7F, 0x80, 0x00]);
+        assert_eq!(
+            lit_pixels(&vm),
+            (9..17).map(|col| (2, col)).collect::<Vec<_>>()
+        );
+        assert!(vm
+            .display
+            .frame_buffer
+            .iter()
+            .flatten()
+            .all(|&pixel| pixel == display::OFF_PIXEL));
+
+        run(&mut vm, 1).unwrap();
+        assert!(vm.mem[layout::VIP_DISPLAY_ADDR..]
+            .iter()
+            .all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn vip_layout_keeps_the_stack_in_ram() {
+        // 2204, then 2208 from 204, then 00EE from 208
+        let rom = [0x22, 0x04, 0x00, 0x00, 0x22, 0x08, 0x00, 0x00, 0x00, 0xEE];
+        let mut vm = vip_layout_vm(&rom, 12);
+        run(&mut vm, 2).unwrap();
+        assert_eq!(vm.mem[0xECC..0xED0], [0x02, 0x06, 0x02, 0x02]);
+
+        // A ROM can overwrite where it returns to
+        vm.mem[0xECC..0xECE].copy_from_slice(&[0x02, 0x02]);
+        run(&mut vm, 1).unwrap();
+        assert_eq!(vm.pc, 0x202);
+    }
+
+    #[test]
+    fn vip_layout_stack_depth() {
+        let rom = [0x22, 0x04, 0x00, 0x00, 0x22, 0x04];
+        let mut vm = vip_layout_vm(&rom, 2);
+        run(&mut vm, 2).unwrap();
+        assert!(matches!(
+            run(&mut vm, 1),
+            Err(error::VmError::Stack {
+                error: stack::StackError::Overflow,
+                depth: 2,
+                ..
+            })
+        ));
+
+        // Stacks deeper than the VIP's 24 calls take room from the ROM
+        let options = |stack_depth| Options {
+            memory_layout: MemoryLayout::Vip,
+            stack_depth: Some(stack_depth),
+            ..Default::default()
+        };
+        assert_eq!(options(12).max_rom_size(), 0xEA0 - 0x200);
+        assert_eq!(options(24).max_rom_size(), 0xEA0 - 0x200);
+        assert_eq!(options(40).max_rom_size(), 0xE80 - 0x200);
+        assert_eq!(options(4096).max_rom_size(), 0);
+    }
+
+    fn machine_code_vm(rom: &[u8], machine_code: MachineCode) -> VM {
+        vm_with(
+            rom,
+            Options {
+                machine_code,
+                memory_layout: MemoryLayout::Vip,
+                ..Default::default()
+            },
+        )
+    }
+
+    // 0204 calls the routine right after it
+    fn call_routine(code: &[u8]) -> Vec<u8> {
+        let mut rom = vec![0x02, 0x04, 0x00, 0x00];
+        rom.extend(code);
+        rom
+    }
+
+    #[test]
+    fn machine_code_policies() {
+        let unknown = call_routine(&[0xF8, 0x10, 0xD4]);
+        let ret = call_routine(&[0xD4]);
+        let error = Err(error::VmError::MachineCode {
+            opcode: 0x0204,
+            addr: 0x204,
+        });
+
+        for (rom, policy, result) in [
+            (&unknown, MachineCode::Ignore, Ok(())),
+            (&ret, MachineCode::Ignore, Ok(())),
+            (&unknown, MachineCode::Error, error.clone()),
+            (&ret, MachineCode::Error, error.clone()),
+            (&unknown, MachineCode::Emulate, error.clone()),
+            (&ret, MachineCode::Emulate, Ok(())),
+        ] {
+            let mut vm = machine_code_vm(rom, policy);
+            assert_eq!(run(&mut vm, 1), result, "{:?} {:X?}", policy, rom);
+        }
+    }
+
+    #[test]
+    fn emulates_clear_screen_code() {
+        let rom = call_routine(&[
+            0x9B, 0xBF, 0xF8, 0x00, 0xAF, 0xF8, 0x00, 0x5F, 0x1F, 0x8F, 0x3A, 0x09, 0xD4,
+        ]);
+        let mut vm = machine_code_vm(&rom, MachineCode::Emulate);
+        vm.set_pixel(31, 63, true);
+        run(&mut vm, 1).unwrap();
+        assert!(vm.mem[layout::VIP_DISPLAY_ADDR..]
+            .iter()
+            .all(|&byte| byte == 0));
+        assert_eq!(vm.pc, 0x202);
+    }
+
+    #[test]
+    fn emulates_waiting_for_the_display_interrupt() {
+        let mut vm = machine_code_vm(&call_routine(&[0x00, 0xD4]), MachineCode::Emulate);
+        run(&mut vm, 1).unwrap();
+        assert!(vm.waiting_for_vblank);
+    }
+
+    #[test]
+    fn only_hires_clears_with_0230() {
+        let mut vm = vm_with(&[0x02, 0x30], Options::default());
+        assert_eq!(
+            run(&mut vm, 1),
+            Err(error::VmError::MachineCode {
+                opcode: 0x0230,
+                addr: 0x230
+            })
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+// Assembles the syntax produced by the disassembler (Cowgod's mnemonics), plus:
+// - Labels ("loop:"), usable anywhere an address or number is expected
+// - Comments starting with ';'
+// - Numbers in decimal, hex ("0x1F", "#1F", "$1F") or binary ("0b1010")
+// - Data directives: "DB 0xF0, 0x90" (bytes) and "DW 0x1234" (big-endian words)
+pub fn assemble(source: &str, start_addr: usize) -> Result<Vec<u8>, String> {
+    // First pass: find the address of every label
+    let mut labels = HashMap::new();
+    let mut addr = start_addr;
+    for (line_num, line) in source.lines().enumerate() {
+        let (label, statement) = split_line(line);
+        if let Some(label) = label {
+            if labels.insert(label.to_lowercase(), addr).is_some() {
+                return Err(format!(
+                    "Line {}: label '{}' defined twice",
+                    line_num + 1,
+                    label
+                ));
+            }
+        }
+
+        if let Some((mnemonic, operands)) = statement {
+            addr += match mnemonic.as_str() {
+                "DB" => operands.len(),
+                "DW" => operands.len() * 2,
+                _ => 2,
+            };
+        }
+    }
+
+    // Second pass: encode each statement now that labels are known
+    let mut output = Vec::new();
+    for (line_num, line) in source.lines().enumerate() {
+        if let (_, Some((mnemonic, operands))) = split_line(line) {
+            encode_statement(&mnemonic, &operands, &labels, &mut output)
+                .map_err(|err| format!("Line {}: {}", line_num + 1, err))?;
+        }
+    }
+
+    Ok(output)
+}
+
+// Split a line into its label (if any) and its statement (mnemonic and operands, if any)
+fn split_line(line: &str) -> (Option<&str>, Option<(String, Vec<&str>)>) {
+    let mut code = line.split(';').next().unwrap().trim();
+    let mut label = None;
+    if let Some((name, rest)) = code.split_once(':') {
+        label = Some(name.trim());
+        code = rest.trim();
+    }
+
+    if code.is_empty() {
+        return (label, None);
+    }
+
+    let (mnemonic, operands) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+    let operands = operands
+        .split(',')
+        .map(str::trim)
+        .filter(|operand| !operand.is_empty())
+        .collect();
+
+    (label, Some((mnemonic.to_uppercase(), operands)))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operand {
+    Reg(u16),
+    Num(u16),
+    I,
+    IndirectI, // [I]
+    DelayTimer,
+    SoundTimer,
+    Key,
+    Font,
+    Bcd,
+}
+
+fn parse_operand(text: &str, labels: &HashMap<String, usize>) -> Result<Operand, String> {
+    let upper = text.to_uppercase();
+    let operand = match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::DelayTimer,
+        "ST" => Operand::SoundTimer,
+        "K" => Operand::Key,
+        "F" => Operand::Font,
+        "B" => Operand::Bcd,
+        _ if upper.len() == 2 && upper.starts_with('V') => {
+            match u16::from_str_radix(&upper[1..], 16) {
+                Ok(reg) => Operand::Reg(reg),
+                Err(_) => return Err(format!("Invalid register '{}'", text)),
+            }
+        }
+        _ => Operand::Num(parse_number(text, labels)?),
+    };
+
+    Ok(operand)
+}
+
+fn parse_number(text: &str, labels: &HashMap<String, usize>) -> Result<u16, String> {
+    let lower = text.to_lowercase();
+    let parsed = if let Some(hex) = lower
+        .strip_prefix("0x")
+        .or_else(|| lower.strip_prefix('#'))
+        .or_else(|| lower.strip_prefix('$'))
+    {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        u16::from_str_radix(binary, 2).ok()
+    } else if lower.starts_with(|c: char| c.is_ascii_digit()) {
+        lower.parse().ok()
+    } else {
+        labels.get(&lower).map(|&addr| addr as u16)
+    };
+
+    parsed.ok_or_else(|| format!("Invalid number or unknown label '{}'", text))
+}
+
+fn encode_statement(
+    mnemonic: &str,
+    operands: &[&str],
+    labels: &HashMap<String, usize>,
+    output: &mut Vec<u8>,
+) -> Result<(), String> {
+    match mnemonic {
+        "DB" | "DW" => {
+            for operand in operands {
+                let value = parse_number(operand, labels)?;
+                if mnemonic == "DB" {
+                    if value > 0xFF {
+                        return Err(format!("Byte value '{}' out of range", operand));
+                    }
+
+                    output.push(value as u8);
+                } else {
+                    output.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+        }
+        _ => {
+            let operands = operands
+                .iter()
+                .map(|operand| parse_operand(operand, labels))
+                .collect::<Result<Vec<_>, _>>()?;
+            let opcode = encode(mnemonic, &operands)?;
+            output.extend_from_slice(&opcode.to_be_bytes());
+        }
+    }
+
+    Ok(())
+}
+
+fn encode(mnemonic: &str, operands: &[Operand]) -> Result<u16, String> {
+    use Operand::*;
+
+    // Range-check immediate values against their field width
+    let addr = |val: u16| check_width(val, 0xFFF);
+    let byte = |val: u16| check_width(val, 0xFF);
+    let xy = |x: u16, y: u16| (x << 8) | (y << 4);
+
+    let opcode = match (mnemonic, operands) {
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("AUDIO", []) => 0xF002,
+        ("SYS", [Num(nnn)]) => addr(*nnn)?,
+        ("JP", [Num(nnn)]) => 0x1000 | addr(*nnn)?,
+        ("JP", [Reg(0), Num(nnn)]) => 0xB000 | addr(*nnn)?,
+        ("CALL", [Num(nnn)]) => 0x2000 | addr(*nnn)?,
+        ("SE", [Reg(x), Num(nn)]) => 0x3000 | (x << 8) | byte(*nn)?,
+        ("SNE", [Reg(x), Num(nn)]) => 0x4000 | (x << 8) | byte(*nn)?,
+        ("SE", [Reg(x), Reg(y)]) => 0x5000 | xy(*x, *y),
+        ("LD", [Reg(x), Num(nn)]) => 0x6000 | (x << 8) | byte(*nn)?,
+        ("ADD", [Reg(x), Num(nn)]) => 0x7000 | (x << 8) | byte(*nn)?,
+        ("LD", [Reg(x), Reg(y)]) => 0x8000 | xy(*x, *y),
+        ("OR", [Reg(x), Reg(y)]) => 0x8001 | xy(*x, *y),
+        ("AND", [Reg(x), Reg(y)]) => 0x8002 | xy(*x, *y),
+        ("XOR", [Reg(x), Reg(y)]) => 0x8003 | xy(*x, *y),
+        ("ADD", [Reg(x), Reg(y)]) => 0x8004 | xy(*x, *y),
+        ("SUB", [Reg(x), Reg(y)]) => 0x8005 | xy(*x, *y),
+        ("SHR", [Reg(x), Reg(y)]) => 0x8006 | xy(*x, *y),
+        ("SHR", [Reg(x)]) => 0x8006 | xy(*x, *x),
+        ("SUBN", [Reg(x), Reg(y)]) => 0x8007 | xy(*x, *y),
+        ("SHL", [Reg(x), Reg(y)]) => 0x800E | xy(*x, *y),
+        ("SHL", [Reg(x)]) => 0x800E | xy(*x, *x),
+        ("SNE", [Reg(x), Reg(y)]) => 0x9000 | xy(*x, *y),
+        ("LD", [I, Num(nnn)]) => 0xA000 | addr(*nnn)?,
+        ("RND", [Reg(x), Num(nn)]) => 0xC000 | (x << 8) | byte(*nn)?,
+        ("DRW", [Reg(x), Reg(y), Num(n)]) => 0xD000 | xy(*x, *y) | check_width(*n, 0xF)?,
+        ("SKP", [Reg(x)]) => 0xE09E | (x << 8),
+        ("SKNP", [Reg(x)]) => 0xE0A1 | (x << 8),
+        ("LD", [Reg(x), DelayTimer]) => 0xF007 | (x << 8),
+        ("LD", [Reg(x), Key]) => 0xF00A | (x << 8),
+        ("LD", [DelayTimer, Reg(x)]) => 0xF015 | (x << 8),
+        ("LD", [SoundTimer, Reg(x)]) => 0xF018 | (x << 8),
+        ("ADD", [I, Reg(x)]) => 0xF01E | (x << 8),
+        ("LD", [Font, Reg(x)]) => 0xF029 | (x << 8),
+        ("LD", [Bcd, Reg(x)]) => 0xF033 | (x << 8),
+        ("PITCH", [Reg(x)]) => 0xF03A | (x << 8),
+        ("LD", [IndirectI, Reg(x)]) => 0xF055 | (x << 8),
+        ("LD", [Reg(x), IndirectI]) => 0xF065 | (x << 8),
+        _ => {
+            return Err(format!(
+                "Invalid instruction or operands for '{}'",
+                mnemonic
+            ))
+        }
+    };
+
+    Ok(opcode)
+}
+
+fn check_width(val: u16, max: u16) -> Result<u16, String> {
+    if val > max {
+        Err(format!("Value 0x{:X} doesn't fit in 0x{:X}", val, max))
+    } else {
+        Ok(val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::disasm::disassemble;
+
+    #[test]
+    fn every_opcode_round_trips_through_disassembly() {
+        let rom: Vec<u8> = (0..=0xFFFFu16).flat_map(u16::to_be_bytes).collect();
+        let source = disassemble(&rom, 0x200).join("\n");
+        assert_eq!(assemble(&source, 0x200), Ok(rom));
+    }
+
+    #[test]
+    fn odd_length_rom_round_trips() {
+        let rom = vec![0x00, 0xE0, 0xF0];
+        let source = disassemble(&rom, 0x200).join("\n");
+        assert_eq!(assemble(&source, 0x200), Ok(rom));
+    }
+
+    #[test]
+    fn labels_numbers_and_data() {
+        let source = "
+            start:  LD V0, #0A      ; hex, with '#'
+                    LD I, sprite
+                    DRW V0, V1, 5
+                    JP start
+            sprite: DB 0b11110000, $90, 144
+                    DW 0x1234
+        ";
+
+        assert_eq!(
+            assemble(source, 0x200),
+            Ok(vec![
+                0x60, 0x0A, 0xA2, 0x08, 0xD0, 0x15, 0x12, 0x00, 0xF0, 0x90, 0x90, 0x12, 0x34,
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_errors_by_line() {
+        assert_eq!(
+            assemble("CLS\nJP nowhere", 0x200),
+            Err(String::from(
+                "Line 2: Invalid number or unknown label 'nowhere'"
+            ))
+        );
+        assert_eq!(
+            assemble("LD V0, 0x100", 0x200),
+            Err(String::from("Line 1: Value 0x100 doesn't fit in 0xFF"))
+        );
+        assert!(assemble("a:\na:", 0x200).is_err());
+    }
+}
@@ -0,0 +1,502 @@
+use std::f32::consts::PI;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::wav;
+
+const SAMPLE_RATE: u32 = 44100;
+const SAMPLES_PER_FRAME: u32 = SAMPLE_RATE / 60;
+
+// Fade in/out over a few milliseconds instead of cutting the wave off mid-cycle,
+// which is what makes the "click" at the start and end of each beep
+const ENVELOPE_LENGTH: f32 = 0.005; // Seconds
+
+// XO-CHIP audio: a 16-byte buffer of 1-bit samples, looped while the sound timer runs
+pub const PATTERN_LEN: usize = 16;
+const PATTERN_BITS: f32 = (PATTERN_LEN * 8) as f32;
+const DEFAULT_PITCH: u8 = 64; // Plays the pattern at 4000 bits/second
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Square, // What the COSMAC VIP's speaker actually produced
+    Sine,
+    Triangle,
+    Noise,
+}
+
+impl std::str::FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "square" => Ok(Waveform::Square),
+            "sine" => Ok(Waveform::Sine),
+            "triangle" => Ok(Waveform::Triangle),
+            "noise" => Ok(Waveform::Noise),
+            _ => Err(format!(
+                "Unknown waveform '{}' (expected square, sine, triangle or noise)",
+                name
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tone {
+    pub frequency: f32, // Hz
+    pub waveform: Waveform,
+    pub volume: f32, // 0.0 to 1.0
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Tone {
+            frequency: 442., // Tune to the "correct" A4 ;)
+            waveform: Waveform::Square,
+            volume: 0.25,
+        }
+    }
+}
+
+// Everything that decides what the buzzer sounds like at a given moment
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Voice {
+    gate: bool, // Sound timer running (and not muted, for live playback)
+    pattern: Option<[u8; PATTERN_LEN]>, // Set once an XO-CHIP ROM loads a pattern
+    pitch: u8,
+}
+
+impl Voice {
+    // Pattern playback rate in bits per second, as defined by XO-CHIP
+    fn pattern_rate(&self) -> f32 {
+        4000. * 2f32.powf((self.pitch as f32 - 64.) / 48.)
+    }
+}
+
+// Generates the buzzer's samples, for both live playback and WAV rendering
+struct Synth {
+    tone: Tone,
+    phase: f32,       // Position within the current cycle, [0, 1)
+    pattern_pos: f32, // Bit position within the pattern buffer, [0, 128)
+    gain: f32,        // Current envelope level, [0, 1]
+    noise_state: u32,
+    noise_sample: f32,
+}
+
+impl Synth {
+    fn new(tone: Tone) -> Self {
+        Synth {
+            tone,
+            phase: 0.,
+            pattern_pos: 0.,
+            gain: 0.,
+            noise_state: 0x1234_5678,
+            noise_sample: 0.,
+        }
+    }
+
+    fn next_sample(&mut self, voice: &Voice) -> f32 {
+        // Ramp the envelope towards on or off
+        let envelope_step = 1. / (ENVELOPE_LENGTH * SAMPLE_RATE as f32);
+        self.gain = if voice.gate {
+            (self.gain + envelope_step).min(1.)
+        } else {
+            (self.gain - envelope_step).max(0.)
+        };
+
+        if self.gain == 0. {
+            // Restart the wave on the next beep
+            self.phase = 0.;
+            self.pattern_pos = 0.;
+            return 0.;
+        }
+
+        if let Some(pattern) = &voice.pattern {
+            // Keep the position when the pattern changes, so patterns swap seamlessly
+            let bit = self.pattern_pos as usize;
+            let wave = if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+                1.
+            } else {
+                -1.
+            };
+
+            self.pattern_pos =
+                (self.pattern_pos + voice.pattern_rate() / SAMPLE_RATE as f32) % PATTERN_BITS;
+            return wave * self.gain * self.tone.volume;
+        }
+
+        let wave = match self.tone.waveform {
+            Waveform::Square => {
+                if self.phase < 0.5 {
+                    1.
+                } else {
+                    -1.
+                }
+            }
+            Waveform::Sine => (self.phase * 2. * PI).sin(),
+            // Shifted a quarter cycle so it starts at 0, like the sine
+            Waveform::Triangle => 1. - 4. * ((self.phase + 0.25).fract() - 0.5).abs(),
+            Waveform::Noise => self.noise_sample,
+        };
+
+        let last_phase = self.phase;
+        self.phase = (self.phase + self.tone.frequency / SAMPLE_RATE as f32).fract();
+
+        // Noise holds a random level for each half-cycle, so the tone frequency still
+        // controls its pitch. Xorshift32, same idea as the ROM's RNG.
+        if (last_phase < 0.5) != (self.phase < 0.5) {
+            self.noise_state ^= self.noise_state << 13;
+            self.noise_state ^= self.noise_state >> 17;
+            self.noise_state ^= self.noise_state << 5;
+            self.noise_sample = (self.noise_state as f32 / u32::MAX as f32) * 2. - 1.;
+        }
+
+        wave * self.gain * self.tone.volume
+    }
+}
+
+// Endless live stream from the synth, following the voice the beeper shares with it
+struct SynthSource {
+    synth: Synth,
+    voice: Arc<Mutex<Voice>>,
+}
+
+impl Iterator for SynthSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let voice = *self.voice.lock().unwrap();
+        Some(self.synth.next_sample(&voice))
+    }
+}
+
+impl rodio::Source for SynthSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+// The buzzer, played through the default audio device if there is one.
+// Without a device (or in headless mode) it's silent, but the buzzer state can still be
+// sampled once per frame and rendered to a WAV file afterwards.
+pub struct Beeper {
+    _stream: Option<rodio::OutputStream>, // Must outlive the sink
+    _sink: Option<rodio::Sink>,
+    tone: Tone,
+    voice: Voice,
+    live_voice: Arc<Mutex<Voice>>, // Shared with the live source
+    muted: bool,
+    frames: Option<Vec<Voice>>, // Buzzer state per frame, if rendering to WAV
+}
+
+impl Beeper {
+    // Falls back to a silent beeper if no audio device is available
+    pub fn new(tone: Tone, render_to_wav: bool) -> Self {
+        let mut beeper = Beeper::silent(tone, render_to_wav);
+        let (stream, stream_handle) = match rodio::OutputStream::try_default() {
+            Ok(output) => output,
+            Err(_) => return beeper,
+        };
+
+        let sink = match rodio::Sink::try_new(&stream_handle) {
+            Ok(sink) => sink,
+            Err(_) => return beeper,
+        };
+
+        // The sink keeps playing the whole time; the synth's envelope silences it,
+        // since pausing the sink abruptly would cut the wave off and click
+        sink.append(SynthSource {
+            synth: Synth::new(tone),
+            voice: Arc::clone(&beeper.live_voice),
+        });
+
+        beeper._stream = Some(stream);
+        beeper._sink = Some(sink);
+        beeper
+    }
+
+    pub fn silent(tone: Tone, render_to_wav: bool) -> Self {
+        let voice = Voice {
+            gate: false,
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+        };
+
+        Beeper {
+            _stream: None,
+            _sink: None,
+            tone,
+            voice,
+            live_voice: Arc::new(Mutex::new(voice)),
+            muted: false,
+            frames: if render_to_wav {
+                Some(Vec::new())
+            } else {
+                None
+            },
+        }
+    }
+
+    pub fn set_playing(&mut self, playing: bool) {
+        self.voice.gate = playing;
+        self.update_live_voice();
+    }
+
+    // F002: from now on, play this pattern instead of the tone
+    pub fn load_pattern(&mut self, pattern: [u8; PATTERN_LEN]) {
+        self.voice.pattern = Some(pattern);
+        self.update_live_voice();
+    }
+
+    // FX3A
+    pub fn set_pitch(&mut self, pitch: u8) {
+        self.voice.pitch = pitch;
+        self.update_live_voice();
+    }
+
+    // Back to the power-on state: silent, with no XO-CHIP pattern
+    pub fn reset(&mut self) {
+        self.voice = Voice {
+            gate: false,
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+        };
+        self.update_live_voice();
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    // Muting only affects the speakers; WAV rendering still gets every beep
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+        self.update_live_voice();
+    }
+
+    fn update_live_voice(&self) {
+        let mut live_voice = self.live_voice.lock().unwrap();
+        *live_voice = self.voice;
+        live_voice.gate &= !self.muted;
+    }
+
+    // Call once per frame to capture the buzzer state for WAV rendering
+    pub fn sample_frame(&mut self) {
+        if let Some(frames) = self.frames.as_mut() {
+            frames.push(self.voice);
+        }
+    }
+
+    // Render every sampled frame into a 16-bit mono WAV file
+    pub fn write_wav(&self, path: &Path) -> Result<(), std::io::Error> {
+        let frames = self.frames.as_deref().unwrap_or_default();
+        let mut samples = Vec::with_capacity(frames.len() * SAMPLES_PER_FRAME as usize);
+        let mut synth = Synth::new(self.tone);
+
+        for voice in frames {
+            for _ in 0..SAMPLES_PER_FRAME {
+                let sample = synth.next_sample(voice);
+                samples.push((sample * i16::MAX as f32) as i16);
+            }
+        }
+
+        wav::write(path, SAMPLE_RATE, &samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Samples of a WAV written by write_wav, after the 44-byte header
+    fn render(beeper: &Beeper, name: &str) -> Vec<i16> {
+        let path = std::env::temp_dir().join(format!("chip8-{}-{}.wav", name, std::process::id()));
+        beeper.write_wav(&path).unwrap();
+        let wav = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        wav[44..]
+            .chunks(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+            .collect()
+    }
+
+    fn voice(gate: bool) -> Voice {
+        Voice {
+            gate,
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+        }
+    }
+
+    // A synth already at full volume, so samples are just the wave
+    fn synth(waveform: Waveform) -> Synth {
+        let mut synth = Synth::new(Tone {
+            frequency: 441., // 100 samples per cycle
+            waveform,
+            volume: 0.5,
+        });
+        synth.gain = 1.;
+        synth
+    }
+
+    fn samples(synth: &mut Synth, voice: &Voice, count: usize) -> Vec<f32> {
+        (0..count).map(|_| synth.next_sample(voice)).collect()
+    }
+
+    fn assert_near(actual: f32, expected: f32, what: &str) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{}: {} != {}",
+            what,
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn pattern_rate_follows_pitch() {
+        for (pitch, rate) in [(64, 4000.), (112, 8000.), (16, 2000.)] {
+            let voice = Voice {
+                pitch,
+                ..voice(true)
+            };
+            assert_near(voice.pattern_rate(), rate, &format!("pitch {}", pitch));
+        }
+    }
+
+    #[test]
+    fn plays_pattern_bits_in_order() {
+        let pattern = [
+            0x80, 0x01, 0xF0, 0x0F, 0xAA, 0x55, 0xC3, 0x3C, 0xFF, 0x00, 0x12, 0x34, 0x56, 0x78,
+            0x9A, 0xBC,
+        ];
+        let voice = Voice {
+            pattern: Some(pattern),
+            ..voice(true)
+        };
+
+        // Twice through, at 4000 bits/second
+        let mut synth = synth(Waveform::Square);
+        let played = samples(
+            &mut synth,
+            &voice,
+            2 * 128 * SAMPLE_RATE as usize / 4000 + 1,
+        );
+        let samples_per_bit = SAMPLE_RATE as f32 / 4000.;
+        for bit in 0..256 {
+            let sample = played[((bit as f32 + 0.5) * samples_per_bit) as usize];
+            let on = pattern[bit % 128 / 8] & (0x80 >> (bit % 8)) != 0;
+            assert_eq!(sample, if on { 0.5 } else { -0.5 }, "bit {}", bit);
+        }
+    }
+
+    #[test]
+    fn square_wave() {
+        let wave = samples(&mut synth(Waveform::Square), &voice(true), 100);
+        // Skip the samples right at the edges, where rounding decides
+        assert!(wave[1..50].iter().all(|&sample| sample == 0.5));
+        assert!(wave[51..].iter().all(|&sample| sample == -0.5));
+    }
+
+    #[test]
+    fn sine_and_triangle_waves() {
+        let sine = samples(&mut synth(Waveform::Sine), &voice(true), 100);
+        let triangle = samples(&mut synth(Waveform::Triangle), &voice(true), 100);
+
+        for n in 0..100 {
+            let phase = n as f32 / 100.;
+            assert_near(sine[n], 0.5 * (phase * 2. * PI).sin(), "sine");
+
+            // Rises from 0 to 1 over the first quarter, down to -1, and back to 0
+            let level = match n {
+                0..=25 => phase * 4.,
+                26..=75 => 2. - phase * 4.,
+                _ => phase * 4. - 4.,
+            };
+            assert_near(triangle[n], 0.5 * level, &format!("triangle {}", n));
+        }
+    }
+
+    #[test]
+    fn noise_holds_a_level_per_half_cycle() {
+        let noise = samples(&mut synth(Waveform::Noise), &voice(true), 200);
+        assert!(noise.iter().all(|sample| sample.abs() <= 0.5));
+
+        // Skip the samples right at the half-cycle edges
+        let levels: Vec<f32> = [1..50, 51..100, 101..150, 151..200]
+            .into_iter()
+            .map(|half| {
+                let level = noise[half.start];
+                assert!(noise[half].iter().all(|&sample| sample == level));
+                level
+            })
+            .collect();
+        assert_eq!(levels[0], 0.); // Nothing drawn yet
+        assert!(levels[1] != levels[2] && levels[2] != levels[3]);
+    }
+
+    #[test]
+    fn envelope_fades_in_and_out() {
+        let mut synth = Synth::new(Tone {
+            frequency: 441.,
+            waveform: Waveform::Square,
+            volume: 1.,
+        });
+        let step = 1. / (ENVELOPE_LENGTH * SAMPLE_RATE as f32);
+
+        // Square wave samples are the envelope level, or minus it
+        let attack = samples(&mut synth, &voice(true), 300);
+        for (n, sample) in attack.iter().enumerate() {
+            let gain = ((n + 1) as f32 * step).min(1.);
+            assert_near(sample.abs(), gain, &format!("attack {}", n));
+        }
+
+        let release = samples(&mut synth, &voice(false), 300);
+        for (n, sample) in release.iter().enumerate() {
+            let gain = (1. - (n + 1) as f32 * step).max(0.);
+            assert_near(sample.abs(), gain, &format!("release {}", n));
+        }
+        assert!(release[230..].iter().all(|&sample| sample == 0.));
+
+        // The next beep starts from the beginning of the wave
+        assert_eq!(synth.phase, 0.);
+    }
+
+    #[test]
+    fn renders_one_frame_of_samples_per_sampled_frame() {
+        let mut beeper = Beeper::silent(Tone::default(), true);
+        beeper.sample_frame();
+        beeper.set_playing(true);
+        beeper.sample_frame();
+
+        let samples = render(&beeper, "frames");
+        let frame = SAMPLES_PER_FRAME as usize;
+        assert_eq!(samples.len(), 2 * frame);
+        assert!(samples[..frame].iter().all(|&sample| sample == 0));
+        assert!(samples[frame..].iter().any(|&sample| sample != 0));
+    }
+
+    #[test]
+    fn muting_still_renders_beeps() {
+        let mut beeper = Beeper::silent(Tone::default(), true);
+        beeper.toggle_mute();
+        beeper.set_playing(true);
+        beeper.sample_frame();
+
+        assert!(render(&beeper, "muted").iter().any(|&sample| sample != 0));
+    }
+}
@@ -0,0 +1,504 @@
+// RCA CDP1802 COSMAC CPU, as used in the COSMAC VIP
+
+// Everything outside the CPU: memory, I/O ports and the EF input flags
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, val: u8);
+    fn output(&mut self, port: u8, val: u8); // OUT 1-7
+    fn input(&mut self, port: u8) -> u8; // INP 1-7
+    fn flag(&self, ef: u8) -> bool; // EF1-EF4
+}
+
+#[derive(Debug, Default)]
+pub struct Cpu {
+    pub r: [u16; 16], // Scratchpad registers
+    pub p: u8,        // Which register is the program counter
+    pub x: u8,        // Which register is the data pointer
+    pub d: u8,        // Accumulator
+    pub df: bool,     // Carry/borrow
+    pub t: u8,        // X and P saved by an interrupt
+    pub ie: bool,     // Interrupts enabled
+    pub q: bool,      // Output flip-flop; drives the VIP's speaker
+    pub idle: bool,   // Waiting for an interrupt or DMA after IDL
+}
+
+impl Cpu {
+    // State after a reset: execution starts at 0x0000 with R0 as the program counter
+    pub fn new() -> Self {
+        Cpu {
+            ie: true,
+            ..Cpu::default()
+        }
+    }
+
+    // Respond to an interrupt request; returns the machine cycles taken
+    pub fn interrupt(&mut self) -> u64 {
+        if !self.ie {
+            return 0;
+        }
+
+        self.t = (self.x << 4) | self.p;
+        self.p = 1;
+        self.x = 2;
+        self.ie = false;
+        self.idle = false;
+        1
+    }
+
+    // One DMA output cycle: the byte at R0 goes to the device, and R0 moves on
+    pub fn dma_out(&mut self, bus: &mut impl Bus) -> u8 {
+        let val = bus.read(self.r[0]);
+        self.r[0] = self.r[0].wrapping_add(1);
+        self.idle = false;
+        val
+    }
+
+    // Run one instruction; returns the machine cycles taken (2, or 3 for long branches
+    // and skips)
+    pub fn step(&mut self, bus: &mut impl Bus) -> u64 {
+        if self.idle {
+            return 1;
+        }
+
+        let opcode = self.fetch(bus);
+        let n = (opcode & 0x0F) as usize;
+        let (p, x) = (self.p as usize, self.x as usize);
+
+        match opcode >> 4 {
+            0x0 if n == 0 => self.idle = true,            // IDL
+            0x0 => self.d = bus.read(self.r[n]),          // LDN
+            0x1 => self.r[n] = self.r[n].wrapping_add(1), // INC
+            0x2 => self.r[n] = self.r[n].wrapping_sub(1), // DEC
+            0x3 => {
+                let taken = match n & 0x7 {
+                    0x0 => true, // BR, and SKP (never) below
+                    0x1 => self.q,
+                    0x2 => self.d == 0,
+                    0x3 => self.df,
+                    ef => bus.flag(ef as u8 - 3),
+                };
+
+                // 0x38-0x3F branch on the opposite condition
+                if taken != (n >= 8) {
+                    self.r[p] = (self.r[p] & 0xFF00) | bus.read(self.r[p]) as u16;
+                } else {
+                    self.r[p] = self.r[p].wrapping_add(1);
+                }
+            }
+            0x4 => {
+                // LDA
+                self.d = bus.read(self.r[n]);
+                self.r[n] = self.r[n].wrapping_add(1);
+            }
+            0x5 => bus.write(self.r[n], self.d), // STR
+            0x6 if n == 0 => self.r[x] = self.r[x].wrapping_add(1), // IRX
+            0x6 if n < 8 => {
+                // OUT
+                let val = bus.read(self.r[x]);
+                self.r[x] = self.r[x].wrapping_add(1);
+                bus.output(n as u8, val);
+            }
+            0x6 if n == 8 => {} // Unused on the 1802
+            0x6 => {
+                // INP
+                let val = bus.input(n as u8 - 8);
+                bus.write(self.r[x], val);
+                self.d = val;
+            }
+            0x7 => self.op_7(n, bus),
+            0x8 => self.d = self.r[n] as u8,        // GLO
+            0x9 => self.d = (self.r[n] >> 8) as u8, // GHI
+            0xA => self.r[n] = (self.r[n] & 0xFF00) | self.d as u16, // PLO
+            0xB => self.r[n] = (self.r[n] & 0x00FF) | ((self.d as u16) << 8), // PHI
+            0xC => {
+                self.long_branch(n, bus);
+                return 3;
+            }
+            0xD => self.p = n as u8, // SEP
+            0xE => self.x = n as u8, // SEX
+            0xF if n & 0x7 == 6 => {
+                // SHR, SHL
+                if n == 6 {
+                    self.df = self.d & 1 != 0;
+                    self.d >>= 1;
+                } else {
+                    self.df = self.d & 0x80 != 0;
+                    self.d <<= 1;
+                }
+            }
+            _ => {
+                // Arithmetic and logic, on M(R(X)) or, from 0xF8 on, the immediate byte
+                let val = if n < 8 {
+                    bus.read(self.r[x])
+                } else {
+                    self.fetch(bus)
+                };
+
+                match n & 0x7 {
+                    0x0 => self.d = val,  // LDX, LDI
+                    0x1 => self.d |= val, // OR, ORI
+                    0x2 => self.d &= val, // AND, ANI
+                    0x3 => self.d ^= val, // XOR, XRI
+                    0x4 => self.add(val, false),
+                    0x5 => self.subtract(val, self.d, true), // SD, SDI: M - D
+                    _ => self.subtract(self.d, val, true),   // SM, SMI: D - M
+                }
+            }
+        }
+
+        2
+    }
+
+    // 0x70-0x7F: interrupt control, stack operations, and arithmetic with carry
+    fn op_7(&mut self, n: usize, bus: &mut impl Bus) {
+        let x = self.x as usize;
+        match n {
+            0x0 | 0x1 => {
+                // RET, DIS
+                let val = bus.read(self.r[x]);
+                self.r[x] = self.r[x].wrapping_add(1);
+                self.x = val >> 4;
+                self.p = val & 0x0F;
+                self.ie = n == 0;
+            }
+            0x2 => {
+                // LDXA
+                self.d = bus.read(self.r[x]);
+                self.r[x] = self.r[x].wrapping_add(1);
+            }
+            0x3 => {
+                // STXD
+                bus.write(self.r[x], self.d);
+                self.r[x] = self.r[x].wrapping_sub(1);
+            }
+            0x4 => self.add(bus.read(self.r[x]), self.df), // ADC
+            0x5 => self.subtract(bus.read(self.r[x]), self.d, self.df), // SDB
+            0x6 => {
+                // SHRC
+                let carry = self.d & 1 != 0;
+                self.d = (self.d >> 1) | ((self.df as u8) << 7);
+                self.df = carry;
+            }
+            0x7 => self.subtract(self.d, bus.read(self.r[x]), self.df), // SMB
+            0x8 => bus.write(self.r[x], self.t),                        // SAV
+            0x9 => {
+                // MARK
+                self.t = (self.x << 4) | self.p;
+                bus.write(self.r[2], self.t);
+                self.x = self.p;
+                self.r[2] = self.r[2].wrapping_sub(1);
+            }
+            0xA => self.q = false, // REQ
+            0xB => self.q = true,  // SEQ
+            0xC => {
+                let val = self.fetch(bus);
+                self.add(val, self.df); // ADCI
+            }
+            0xD => {
+                let val = self.fetch(bus);
+                self.subtract(val, self.d, self.df); // SDBI
+            }
+            0xE => {
+                // SHLC
+                let carry = self.d & 0x80 != 0;
+                self.d = (self.d << 1) | self.df as u8;
+                self.df = carry;
+            }
+            _ => {
+                let val = self.fetch(bus);
+                self.subtract(self.d, val, self.df); // SMBI
+            }
+        }
+    }
+
+    // 0xC0-0xCF: long branches to the next two bytes, and long skips over them
+    fn long_branch(&mut self, n: usize, bus: &mut impl Bus) {
+        let p = self.p as usize;
+        let condition = match n & 0x3 {
+            0x0 => true,
+            0x1 => self.q,
+            0x2 => self.d == 0,
+            _ => self.df,
+        };
+
+        match n {
+            0x4 => {} // NOP
+            0xC => {
+                // LSIE
+                if self.ie {
+                    self.r[p] = self.r[p].wrapping_add(2);
+                }
+            }
+            // LBR, LBQ, LBZ, LBDF, and their opposites from 0xC8 on (0xC8 is LSKP)
+            0x0..=0x3 | 0x8..=0xB => {
+                let taken = condition != (n >= 8);
+                if n == 0x8 {
+                    self.r[p] = self.r[p].wrapping_add(2);
+                } else if taken {
+                    let high = bus.read(self.r[p]) as u16;
+                    let low = bus.read(self.r[p].wrapping_add(1)) as u16;
+                    self.r[p] = (high << 8) | low;
+                } else {
+                    self.r[p] = self.r[p].wrapping_add(2);
+                }
+            }
+            // LSNQ, LSNZ, LSNF, then LSQ, LSZ, LSDF
+            _ => {
+                if condition == (n >= 0xC) {
+                    self.r[p] = self.r[p].wrapping_add(2);
+                }
+            }
+        }
+    }
+
+    fn fetch(&mut self, bus: &mut impl Bus) -> u8 {
+        let p = self.p as usize;
+        let val = bus.read(self.r[p]);
+        self.r[p] = self.r[p].wrapping_add(1);
+        val
+    }
+
+    fn add(&mut self, val: u8, carry: bool) {
+        let sum = self.d as u16 + val as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    // DF = 1 means no borrow
+    fn subtract(&mut self, minuend: u8, subtrahend: u8, no_borrow: bool) {
+        let difference = minuend as i16 - subtrahend as i16 - (!no_borrow) as i16;
+        self.d = difference as u8;
+        self.df = difference >= 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 64KB of RAM, with the outputs kept for checking
+    struct TestBus {
+        mem: Vec<u8>,
+        outputs: Vec<(u8, u8)>,
+        flags: [bool; 4],
+    }
+
+    impl Bus for TestBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            self.mem[addr as usize]
+        }
+
+        fn write(&mut self, addr: u16, val: u8) {
+            self.mem[addr as usize] = val;
+        }
+
+        fn output(&mut self, port: u8, val: u8) {
+            self.outputs.push((port, val));
+        }
+
+        fn input(&mut self, port: u8) -> u8 {
+            0x50 | port
+        }
+
+        fn flag(&self, ef: u8) -> bool {
+            self.flags[ef as usize - 1]
+        }
+    }
+
+    fn load(program: &[(u16, &[u8])]) -> (Cpu, TestBus) {
+        let mut bus = TestBus {
+            mem: vec![0; 0x10000],
+            outputs: Vec::new(),
+            flags: [false; 4],
+        };
+
+        for (addr, code) in program {
+            let addr = *addr as usize;
+            bus.mem[addr..addr + code.len()].copy_from_slice(code);
+        }
+
+        (Cpu::new(), bus)
+    }
+
+    #[test]
+    fn arithmetic_sets_df() {
+        let (mut cpu, mut bus) = load(&[(
+            0x00,
+            &[
+                0xF8, 0xF0, // LDI F0
+                0xFC, 0x20, // ADI 20
+                0x7C, 0x01, // ADCI 01
+                0xFD, 0x11, // SDI 11
+                0xFF, 0x0F, // SMI 0F
+                0x7F, 0xF0, // SMBI F0
+            ],
+        )]);
+
+        // D and DF after each instruction; DF = 1 means a carry, or no borrow
+        let expected = [
+            (0xF0, false),
+            (0x10, true),
+            (0x12, false),
+            (0xFF, false),
+            (0xF0, true),
+            (0x00, true),
+        ];
+
+        for (d, df) in expected {
+            assert_eq!(cpu.step(&mut bus), 2);
+            assert_eq!((cpu.d, cpu.df), (d, df));
+        }
+    }
+
+    #[test]
+    fn shifts_through_df() {
+        let (mut cpu, mut bus) = load(&[(
+            0x00,
+            &[
+                0xF8, 0x81, // LDI 81
+                0x76, // SHRC
+                0x76, // SHRC
+                0x7E, // SHLC
+                0x7E, // SHLC
+                0xF6, // SHR
+                0xFE, // SHL
+            ],
+        )]);
+
+        let expected = [
+            (0x81, false),
+            (0x40, true),
+            (0xA0, false),
+            (0x40, true),
+            (0x81, false),
+            (0x40, true),
+            (0x80, false),
+        ];
+
+        for (d, df) in expected {
+            cpu.step(&mut bus);
+            assert_eq!((cpu.d, cpu.df), (d, df));
+        }
+    }
+
+    #[test]
+    fn short_branches() {
+        let (mut cpu, mut bus) = load(&[
+            (0x00, &[0x30, 0x10]),             // BR 10
+            (0x10, &[0x32, 0x20]),             // BZ 20: D is 0
+            (0x20, &[0xF8, 0x01, 0x32, 0x00]), // LDI 01, BZ 00: not taken
+            (0x24, &[0x3A, 0x30]),             // BNZ 30
+            (0x30, &[0x34, 0x00, 0x3C, 0x40]), // B1 00: EF1 is off, BN1 40
+            (0x40, &[0x38, 0x00]),             // SKP
+        ]);
+
+        for pc in [0x10, 0x20, 0x22, 0x24, 0x30, 0x32, 0x40, 0x42] {
+            cpu.step(&mut bus);
+            assert_eq!(cpu.r[0], pc);
+        }
+
+        // Short branches stay on the same page
+        bus.flags[0] = true;
+        cpu.r[0] = 0x1FE;
+        bus.mem[0x1FE..0x200].copy_from_slice(&[0x34, 0x80]); // B1 80
+        cpu.step(&mut bus);
+        assert_eq!(cpu.r[0], 0x180);
+    }
+
+    #[test]
+    fn long_branches_and_skips() {
+        let (mut cpu, mut bus) = load(&[
+            (0x0000, &[0xC0, 0x12, 0x34]),             // LBR 1234
+            (0x1234, &[0xCA, 0x00, 0x00, 0xC5]),       // LBNZ: D is 0, LSNQ
+            (0x123A, &[0x7B, 0xCD, 0xF8, 0xF8, 0xC4]), // SEQ, LSQ, (skipped), NOP
+        ]);
+
+        for pc in [0x1234, 0x1237, 0x123A, 0x123B, 0x123E, 0x123F] {
+            let cycles = cpu.step(&mut bus);
+            assert_eq!(cpu.r[0], pc);
+            assert_eq!(cycles, if pc == 0x123B { 2 } else { 3 });
+        }
+    }
+
+    #[test]
+    fn memory_and_io() {
+        let (mut cpu, mut bus) = load(&[(
+            0x00,
+            &[
+                0xE3, // SEX 3
+                0xF8, 0xAA, // LDI AA
+                0x53, // STR R3
+                0x63, // OUT 3: M(R3), then R3 + 1
+                0x6C, // INP 4: into M(R3) and D
+                0x43, // LDA R3
+                0x73, // STXD
+            ],
+        )]);
+        cpu.r[3] = 0x80;
+
+        for _ in 0..8 {
+            cpu.step(&mut bus);
+        }
+
+        assert_eq!(bus.outputs, [(3, 0xAA)]);
+        assert_eq!(bus.mem[0x80..0x83], [0xAA, 0x54, 0x54]);
+        assert_eq!(cpu.d, 0x54);
+        assert_eq!(cpu.r[3], 0x81);
+    }
+
+    #[test]
+    fn interrupt_and_return() {
+        let (mut cpu, mut bus) = load(&[
+            (0x00, &[0xE5, 0x00]), // SEX 5, IDL
+            (0x40, &[0x78, 0x70]), // SAV, RET
+        ]);
+        cpu.r[1] = 0x40;
+        cpu.r[2] = 0x80;
+
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert!(cpu.idle);
+        assert_eq!(cpu.step(&mut bus), 1);
+        assert_eq!(cpu.r[0], 0x02);
+
+        // Saves X and P in T, then runs R1 with interrupts off
+        assert_eq!(cpu.interrupt(), 1);
+        assert_eq!(
+            (cpu.t, cpu.p, cpu.x, cpu.ie, cpu.idle),
+            (0x50, 1, 2, false, false)
+        );
+        assert_eq!(cpu.interrupt(), 0);
+
+        cpu.step(&mut bus);
+        assert_eq!(bus.mem[0x80], 0x50);
+        cpu.step(&mut bus);
+        assert_eq!((cpu.p, cpu.x, cpu.ie, cpu.r[2]), (0, 5, true, 0x81));
+    }
+
+    #[test]
+    fn mark_saves_x_and_p() {
+        let (mut cpu, mut bus) = load(&[(0x00, &[0xE3, 0x79, 0x71, 0x71])]); // SEX 3, MARK, DIS 71
+        cpu.r[2] = 0x80;
+
+        cpu.step(&mut bus);
+        cpu.step(&mut bus);
+        assert_eq!((cpu.t, cpu.x, cpu.r[2]), (0x30, 0, 0x7F));
+        assert_eq!(bus.mem[0x80], 0x30);
+
+        // DIS with X = P reads the next byte as X and P, and disables interrupts
+        cpu.step(&mut bus);
+        assert_eq!((cpu.x, cpu.p, cpu.ie, cpu.r[0]), (7, 1, false, 0x04));
+    }
+
+    #[test]
+    fn dma_wakes_from_idle() {
+        let (mut cpu, mut bus) = load(&[(0x00, &[0x00]), (0x200, &[0x42])]);
+        cpu.step(&mut bus);
+        assert!(cpu.idle);
+
+        cpu.r[0] = 0x200;
+        assert_eq!(cpu.dma_out(&mut bus), 0x42);
+        assert_eq!(cpu.r[0], 0x201);
+        assert!(!cpu.idle);
+    }
+}
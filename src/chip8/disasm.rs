@@ -0,0 +1,75 @@
+// Mnemonics follow Cowgod's CHIP-8 technical reference, which most CHIP-8 tools use.
+// Each line is valid input for the assembler, with the address and raw opcode in a comment.
+pub fn disassemble(rom: &[u8], start_addr: usize) -> Vec<String> {
+    let mut lines = Vec::with_capacity(rom.len() / 2 + 1);
+    for (i, bytes) in rom.chunks(2).enumerate() {
+        let addr = start_addr + i * 2;
+        let line = match bytes {
+            [high, low] => {
+                let opcode = ((*high as u16) << 8) | (*low as u16);
+                format!("    {:<20}; {:03X}: {:04X}", mnemonic(opcode), addr, opcode)
+            }
+            // ROMs with an odd length end with a lone data byte
+            _ => format!(
+                "    {:<20}; {:03X}: {:02X}",
+                format!("DB 0x{:02X}", bytes[0]),
+                addr,
+                bytes[0]
+            ),
+        };
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+pub fn mnemonic(opcode: u16) -> String {
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let n = opcode & 0x000F;
+    let nn = opcode & 0x00FF;
+    let nnn = opcode & 0x0FFF;
+
+    match (opcode >> 12, x, y, n) {
+        (0x0, 0x0, 0xE, 0x0) => String::from("CLS"),
+        (0x0, 0x0, 0xE, 0xE) => String::from("RET"),
+        (0x0, ..) => format!("SYS 0x{:03X}", nnn),
+        (0x1, ..) => format!("JP 0x{:03X}", nnn),
+        (0x2, ..) => format!("CALL 0x{:03X}", nnn),
+        (0x3, ..) => format!("SE V{:X}, 0x{:02X}", x, nn),
+        (0x4, ..) => format!("SNE V{:X}, 0x{:02X}", x, nn),
+        (0x5, _, _, 0x0) => format!("SE V{:X}, V{:X}", x, y),
+        (0x6, ..) => format!("LD V{:X}, 0x{:02X}", x, nn),
+        (0x7, ..) => format!("ADD V{:X}, 0x{:02X}", x, nn),
+        (0x8, _, _, 0x0) => format!("LD V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x1) => format!("OR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x2) => format!("AND V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x3) => format!("XOR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x4) => format!("ADD V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x5) => format!("SUB V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x6) => format!("SHR V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0x7) => format!("SUBN V{:X}, V{:X}", x, y),
+        (0x8, _, _, 0xE) => format!("SHL V{:X}, V{:X}", x, y),
+        (0x9, _, _, 0x0) => format!("SNE V{:X}, V{:X}", x, y),
+        (0xA, ..) => format!("LD I, 0x{:03X}", nnn),
+        (0xB, ..) => format!("JP V0, 0x{:03X}", nnn),
+        (0xC, ..) => format!("RND V{:X}, 0x{:02X}", x, nn),
+        (0xD, ..) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        (0xE, _, 0x9, 0xE) => format!("SKP V{:X}", x),
+        (0xE, _, 0xA, 0x1) => format!("SKNP V{:X}", x),
+        (0xF, 0x0, 0x0, 0x2) => String::from("AUDIO"),
+        (0xF, _, 0x0, 0x7) => format!("LD V{:X}, DT", x),
+        (0xF, _, 0x0, 0xA) => format!("LD V{:X}, K", x),
+        (0xF, _, 0x1, 0x5) => format!("LD DT, V{:X}", x),
+        (0xF, _, 0x1, 0x8) => format!("LD ST, V{:X}", x),
+        (0xF, _, 0x1, 0xE) => format!("ADD I, V{:X}", x),
+        (0xF, _, 0x2, 0x9) => format!("LD F, V{:X}", x),
+        (0xF, _, 0x3, 0x3) => format!("LD B, V{:X}", x),
+        (0xF, _, 0x3, 0xA) => format!("PITCH V{:X}", x),
+        (0xF, _, 0x5, 0x5) => format!("LD [I], V{:X}", x),
+        (0xF, _, 0x6, 0x5) => format!("LD V{:X}, [I]", x),
+        // Not an instruction; most likely sprite data
+        _ => format!("DW 0x{:04X}", opcode),
+    }
+}
@@ -1,15 +1,91 @@
 use std::io::{stdout, BufWriter, Stdout, Write};
 
+use termion::color::{Bg, Fg, Reset, Rgb};
 use termion::raw::{IntoRawMode, RawTerminal};
 use termion::screen::{AlternateScreen, IntoAlternateScreen};
 
-const DISPLAY_HEIGHT: usize = 32;
+use super::graphics::{self, Renderer};
+use super::platform::ColorBoard;
+use super::shutdown;
+
 const DISPLAY_WIDTH: usize = 64;
+const MAX_DISPLAY_HEIGHT: usize = 64; // HIRES; other platforms only use the top 32 rows
 pub const OFF_PIXEL: char = ' ';
 pub const ON_PIXEL: char = '█'; // U+2588 FULL BLOCK
 
+type Output = BufWriter<RawTerminal<AlternateScreen<Stdout>>>;
+
+// Colors used wherever the frame is rendered to an image (e.g. GIF recordings)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub off: [u8; 3],
+    pub on: [u8; 3],
+}
+
+impl Palette {
+    // Parse palette from "RRGGBB,RRGGBB" (off color, then on color)
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (off, on) = text
+            .split_once(',')
+            .ok_or_else(|| format!("Palette '{}' should look like RRGGBB,RRGGBB", text))?;
+
+        Ok(Palette {
+            off: parse_color(off)?,
+            on: parse_color(on)?,
+        })
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            off: [0x00, 0x00, 0x00],
+            on: [0xFF, 0xFF, 0xFF],
+        }
+    }
+}
+
+fn parse_color(text: &str) -> Result<[u8; 3], String> {
+    let text = text.trim().trim_start_matches('#');
+    if text.len() != 6 {
+        return Err(format!("Invalid color '{}'", text));
+    }
+
+    let mut color = [0; 3];
+    for (i, channel) in color.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(&text[i * 2..i * 2 + 2], 16)
+            .map_err(|_| format!("Invalid color '{}'", text))?;
+    }
+
+    Ok(color)
+}
+
+// How the frame fits in the terminal, recomputed whenever the terminal is resized
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Layout {
+    Fits {
+        left: u16, // 1-based terminal column and row of the top-left corner
+        top: u16,
+        scale_x: usize, // Terminal cells per pixel
+        scale_y: usize,
+        half_blocks: bool, // Two pixel rows per terminal row
+        border: bool,
+    },
+    // Sixel or kitty image, drawn at its own scale
+    Image {
+        left: u16,
+        top: u16,
+    },
+    TooSmall,
+}
+
+const BORDER_WIDTH: usize = 3; // Two border blocks and a space of padding, on each side
+const BORDER_HEIGHT: usize = 2; // One border block and a row of padding, above and below
+
 pub struct Display {
-    pub frame_buffer: [[char; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+    pub frame_buffer: [[char; DISPLAY_WIDTH]; MAX_DISPLAY_HEIGHT],
+    height: usize,
+    pub colors: Option<ColorBoard>, // CHIP-8X; only the text renderer shows them
     // Lots going on here:
     // - Use buffer when writing to stdout to avoid unnecessary syscalls
     //   (BufWriter defaults to 8 KB buffer at time of writing, which is
@@ -18,85 +94,452 @@ pub struct Display {
     //   (i.e. user doesn't need to press enter to send input to stdin)
     // - Use termion's AlternateScreen to separate emulator output
     //   from rest of terminal history
-    output: BufWriter<RawTerminal<AlternateScreen<Stdout>>>,
+    // - No output at all in headless mode, where frames are only recorded
+    output: Option<Output>,
+    renderer: Renderer,
+    image_scale: usize, // Image pixels per CHIP-8 pixel, for the graphics renderers
+    palette: Palette,
+    terminal_size: (u16, u16),
+    layout: Layout,
+    needs_clear: bool,
+    status: Option<String>, // Shown on the line below the frame; None if disabled
 }
 
 impl Display {
-    pub fn new() -> Self {
+    pub fn new(
+        height: usize,
+        renderer: Renderer,
+        image_scale: usize,
+        palette: Palette,
+        status_bar: bool,
+    ) -> Self {
+        shutdown::save_terminal();
         Display {
-            frame_buffer: [[OFF_PIXEL; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
-            output: BufWriter::new(
+            renderer,
+            image_scale,
+            palette,
+            status: status_bar.then(String::new),
+            output: Some(BufWriter::new(
                 stdout()
                     .into_alternate_screen()
                     .unwrap()
                     .into_raw_mode()
                     .unwrap(),
-            ),
+            )),
+            ..Display::headless(height)
+        }
+    }
+
+    // Frame buffer only; leaves the terminal untouched
+    pub fn headless(height: usize) -> Self {
+        debug_assert!(height <= MAX_DISPLAY_HEIGHT, "Invalid display height!");
+        Display {
+            frame_buffer: [[OFF_PIXEL; DISPLAY_WIDTH]; MAX_DISPLAY_HEIGHT],
+            height,
+            colors: None,
+            output: None,
+            renderer: Renderer::Text,
+            image_scale: 1,
+            palette: Palette::default(),
+            terminal_size: (0, 0),
+            layout: Layout::TooSmall,
+            needs_clear: true,
+            status: None,
         }
     }
 
+    pub fn is_headless(&self) -> bool {
+        self.output.is_none()
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Row-major copy of the frame buffer, true where a pixel is on
+    pub fn pixels(&self) -> Vec<bool> {
+        self.frame_buffer[..self.height]
+            .iter()
+            .flatten()
+            .map(|&pixel| pixel == ON_PIXEL)
+            .collect()
+    }
+
+    // Set every pixel from a screen kept elsewhere; true if anything changed
+    pub fn update(&mut self, pixel: impl Fn(usize, usize) -> bool) -> bool {
+        let mut changed = false;
+        for (row, line) in self.frame_buffer[..self.height].iter_mut().enumerate() {
+            for (col, cell) in line.iter_mut().enumerate() {
+                let new = if pixel(row, col) { ON_PIXEL } else { OFF_PIXEL };
+                changed |= *cell != new;
+                *cell = new;
+            }
+        }
+
+        changed
+    }
+
+    // Check the terminal size (cheap enough to do every frame); true if the frame
+    // needs to be redrawn to fit a new size
+    pub fn poll_resize(&mut self) -> bool {
+        if self.is_headless() {
+            return false;
+        }
+
+        let size = termion::terminal_size().unwrap_or((0, 0));
+        if size == self.terminal_size {
+            return false;
+        }
+
+        self.terminal_size = size;
+
+        // Keep the last line free for the status bar
+        let rows = size.1.saturating_sub(self.status.is_some() as u16);
+        self.layout = match self.renderer {
+            Renderer::Text => Layout::fit(size.0 as usize, rows as usize, self.height),
+            _ => Layout::image((size.0, rows), self.image_scale, self.height),
+        };
+        self.needs_clear = true;
+        true
+    }
+
     pub fn draw(&mut self) -> Result<(), std::io::Error> {
-        // Reset cursor
-        write!(self.output, "{}", termion::cursor::Goto(1, 1))?;
+        if self.needs_clear {
+            self.poll_resize();
+        }
+
+        let output = match self.output.as_mut() {
+            Some(output) => output,
+            None => return Ok(()),
+        };
+
+        if self.needs_clear {
+            self.needs_clear = false;
+            write!(output, "{}", termion::clear::All)?;
+        }
+
+        let (left, top, scale_x, scale_y, half_blocks, border) = match self.layout {
+            Layout::Fits {
+                left,
+                top,
+                scale_x,
+                scale_y,
+                half_blocks,
+                border,
+            } => (left, top, scale_x, scale_y, half_blocks, border),
+            Layout::Image { left, top } => {
+                let encode = match self.renderer {
+                    Renderer::Kitty => graphics::encode_kitty,
+                    _ => graphics::encode_sixel,
+                };
+
+                let pixels: Vec<bool> = self.frame_buffer[..self.height]
+                    .iter()
+                    .flatten()
+                    .map(|&pixel| pixel == ON_PIXEL)
+                    .collect();
+                let image = encode(
+                    &pixels,
+                    DISPLAY_WIDTH,
+                    self.height,
+                    self.image_scale,
+                    self.palette,
+                );
+
+                write!(output, "{}{}", termion::cursor::Goto(left, top), image)?;
+                Self::draw_status(
+                    output,
+                    &self.status,
+                    self.terminal_size,
+                    1,
+                    self.terminal_size.1.max(1),
+                )?;
+                return output.flush();
+            }
+            Layout::TooSmall => {
+                let (cols, rows) = self.terminal_size;
+                write!(
+                    output,
+                    "{}Terminal too small ({}x{})\r\nNeed at least {}x{}",
+                    termion::cursor::Goto(1, 1),
+                    cols,
+                    rows,
+                    DISPLAY_WIDTH,
+                    self.height / 2
+                )?;
+                return output.flush();
+            }
+        };
 
-        self.draw_top_border()?;
+        // Each line of the frame, without borders
+        let frame = &self.frame_buffer[..self.height];
+        let mut lines = Vec::with_capacity(self.height * scale_y);
+        if let Some(colors) = &self.colors {
+            // Upper half blocks, colored as the pixels above and below
+            let color =
+                |row: usize, col: usize| colors.color(row, col, frame[row][col] == ON_PIXEL);
 
-        // Write frame
-        for row in self.frame_buffer.iter() {
-            write!(self.output, "{}{} ", ON_PIXEL, ON_PIXEL)?;
-            for pixel in row {
-                write!(self.output, "{}", pixel)?;
+            let rows_per_line = if half_blocks { 2 } else { 1 };
+            for row in (0..self.height).step_by(rows_per_line) {
+                let line = colored_line((0..DISPLAY_WIDTH).flat_map(|col| {
+                    let cell = (color(row, col), color(row + rows_per_line - 1, col));
+                    std::iter::repeat_n(cell, if half_blocks { 1 } else { scale_x })
+                }));
+                lines.extend(std::iter::repeat_n(
+                    line,
+                    if half_blocks { 1 } else { scale_y },
+                ));
+            }
+        } else if half_blocks {
+            for rows in frame.chunks(2) {
+                lines.push(
+                    rows[0]
+                        .iter()
+                        .zip(rows[1].iter())
+                        .map(|(&upper, &lower)| half_block(upper == ON_PIXEL, lower == ON_PIXEL))
+                        .collect::<String>(),
+                );
             }
+        } else {
+            for row in frame.iter() {
+                let line: String = row
+                    .iter()
+                    .flat_map(|&pixel| std::iter::repeat_n(pixel, scale_x))
+                    .collect();
+                lines.extend(std::iter::repeat_n(line, scale_y));
+            }
+        }
 
-            write!(self.output, " {}{}\r\n", ON_PIXEL, ON_PIXEL)?;
+        let width = DISPLAY_WIDTH * scale_x;
+        let mut row = top;
+        if border {
+            Self::draw_top_border(output, left, row, width)?;
+            row += BORDER_HEIGHT as u16;
         }
 
-        self.draw_bottom_border()?;
+        for line in lines.iter() {
+            write!(output, "{}", termion::cursor::Goto(left, row))?;
+            if border {
+                write!(
+                    output,
+                    "{}{} {} {}{}",
+                    ON_PIXEL, ON_PIXEL, line, ON_PIXEL, ON_PIXEL
+                )?;
+            } else {
+                write!(output, "{}", line)?;
+            }
 
-        // One last carriage return
-        write!(self.output, "\r\n\n")?;
+            row += 1;
+        }
+
+        if border {
+            Self::draw_bottom_border(output, left, row, width)?;
+            row += BORDER_HEIGHT as u16;
+        }
+
+        Self::draw_status(output, &self.status, self.terminal_size, left, row)?;
+
+        // Leave the cursor below the frame
+        write!(output, "{}", termion::cursor::Goto(1, row))?;
 
         // Flush the entire frame to stdout, with just one syscall
-        self.output.flush()?;
+        output.flush()?;
         Ok(())
     }
 
-    fn draw_top_border(&mut self) -> Result<(), std::io::Error> {
-        // Draw top border
-        for _ in 0..(DISPLAY_WIDTH + 6) {
-            write!(self.output, "{}", ON_PIXEL)?;
+    // Set the status bar's text; true if it changed and needs to be drawn
+    pub fn set_status(&mut self, text: String) -> bool {
+        match self.status.as_mut() {
+            Some(status) if *status != text => {
+                *status = text;
+                true
+            }
+            _ => false,
         }
+    }
 
-        // Write extra padding below top border
-        write!(self.output, "\r\n{}{} ", ON_PIXEL, ON_PIXEL)?;
-        for _ in 0..DISPLAY_WIDTH {
-            write!(self.output, "{}", OFF_PIXEL)?;
+    fn draw_status(
+        output: &mut Output,
+        status: &Option<String>,
+        terminal_size: (u16, u16),
+        left: u16,
+        row: u16,
+    ) -> Result<(), std::io::Error> {
+        if let Some(status) = status {
+            // Cut off at the edge of the terminal instead of wrapping
+            let max_width = terminal_size.0.saturating_sub(left - 1) as usize;
+            let status: String = status.chars().take(max_width).collect();
+            write!(
+                output,
+                "{}{}{}",
+                termion::cursor::Goto(left, row),
+                status,
+                termion::clear::UntilNewline
+            )?;
         }
 
-        write!(self.output, " {}{}\r\n", ON_PIXEL, ON_PIXEL)?;
         Ok(())
     }
 
-    fn draw_bottom_border(&mut self) -> Result<(), std::io::Error> {
-        // Write extra padding above bottom border
-        write!(self.output, "{}{} ", ON_PIXEL, ON_PIXEL)?;
-        for _ in 0..DISPLAY_WIDTH {
-            write!(self.output, "{}", OFF_PIXEL)?;
-        }
+    fn draw_top_border(
+        output: &mut Output,
+        left: u16,
+        top: u16,
+        width: usize,
+    ) -> Result<(), std::io::Error> {
+        // Draw top border
+        let border: String = std::iter::repeat_n(ON_PIXEL, width + BORDER_WIDTH * 2).collect();
+        write!(output, "{}{}", termion::cursor::Goto(left, top), border)?;
 
-        write!(self.output, " {}{}\r\n", ON_PIXEL, ON_PIXEL)?;
+        // Write extra padding below top border
+        let padding: String = std::iter::repeat_n(OFF_PIXEL, width).collect();
+        write!(
+            output,
+            "{}{}{} {} {}{}",
+            termion::cursor::Goto(left, top + 1),
+            ON_PIXEL,
+            ON_PIXEL,
+            padding,
+            ON_PIXEL,
+            ON_PIXEL
+        )?;
+        Ok(())
+    }
 
-        // Draw bottom border
-        for _ in 0..(DISPLAY_WIDTH + 6) {
-            write!(self.output, "{}", ON_PIXEL)?;
-        }
+    fn draw_bottom_border(
+        output: &mut Output,
+        left: u16,
+        top: u16,
+        width: usize,
+    ) -> Result<(), std::io::Error> {
+        // Write extra padding above bottom border
+        let padding: String = std::iter::repeat_n(OFF_PIXEL, width).collect();
+        write!(
+            output,
+            "{}{}{} {} {}{}",
+            termion::cursor::Goto(left, top),
+            ON_PIXEL,
+            ON_PIXEL,
+            padding,
+            ON_PIXEL,
+            ON_PIXEL
+        )?;
 
+        // Draw bottom border
+        let border: String = std::iter::repeat_n(ON_PIXEL, width + BORDER_WIDTH * 2).collect();
+        write!(output, "{}{}", termion::cursor::Goto(left, top + 1), border)?;
         Ok(())
     }
 
     // Only let debug mode use the output buffer in debug builds
     #[cfg(debug_assertions)]
-    pub fn borrow_output_buf(&mut self) -> &mut BufWriter<RawTerminal<AlternateScreen<Stdout>>> {
-        &mut self.output
+    pub fn borrow_output_buf(&mut self) -> &mut Output {
+        self.output
+            .as_mut()
+            .expect("Debug mode requires a terminal!")
     }
 }
+
+impl Drop for Display {
+    // The terminal is restored as the output is dropped, so there's nothing left to do on a panic
+    fn drop(&mut self) {
+        if self.output.is_some() {
+            shutdown::forget_terminal();
+        }
+    }
+}
+
+impl Layout {
+    // Biggest integer scale that fits, centered. Characters are about twice as tall as they
+    // are wide, so pixels are at most two cells wide per cell of height. Borders are dropped
+    // before switching to half blocks, which are dropped before giving up.
+    fn fit(cols: usize, rows: usize, display_height: usize) -> Layout {
+        for (half_blocks, border) in [(false, true), (false, false), (true, true), (true, false)] {
+            let (border_cols, border_rows) = if border {
+                (BORDER_WIDTH * 2, BORDER_HEIGHT * 2)
+            } else {
+                (0, 0)
+            };
+
+            let frame_rows = if half_blocks {
+                display_height / 2
+            } else {
+                display_height
+            };
+
+            let fit_x = cols.saturating_sub(border_cols) / DISPLAY_WIDTH;
+            let fit_y = rows.saturating_sub(border_rows) / frame_rows;
+            if fit_x == 0 || fit_y == 0 {
+                continue;
+            }
+
+            let (scale_x, scale_y) = if half_blocks {
+                (1, 1)
+            } else {
+                let scale_y = fit_y.min(fit_x);
+                (fit_x.min(scale_y * 2), scale_y)
+            };
+
+            let width = DISPLAY_WIDTH * scale_x + border_cols;
+            let height = frame_rows * scale_y + border_rows;
+            return Layout::Fits {
+                left: ((cols - width) / 2 + 1) as u16,
+                top: ((rows - height) / 2 + 1) as u16,
+                scale_x,
+                scale_y,
+                half_blocks,
+                border,
+            };
+        }
+
+        Layout::TooSmall
+    }
+
+    // Centered if the terminal reports its size in pixels, otherwise in the top-left corner
+    fn image(size: (u16, u16), scale: usize, display_height: usize) -> Layout {
+        let (cols, rows) = (size.0 as usize, size.1 as usize);
+        match termion::terminal_size_pixels() {
+            Ok((width, height)) if cols > 0 && rows > 0 && width > 0 && height > 0 => {
+                let image_cols = (DISPLAY_WIDTH * scale)
+                    .div_ceil(width as usize / cols)
+                    .max(1);
+                let image_rows = (display_height * scale)
+                    .div_ceil(height as usize / rows)
+                    .max(1);
+                Layout::Image {
+                    left: (cols.saturating_sub(image_cols) / 2 + 1) as u16,
+                    top: (rows.saturating_sub(image_rows) / 2 + 1) as u16,
+                }
+            }
+            _ => Layout::Image { left: 1, top: 1 },
+        }
+    }
+}
+
+fn half_block(upper: bool, lower: bool) -> char {
+    match (upper, lower) {
+        (true, true) => ON_PIXEL,
+        (true, false) => '▀', // U+2580 UPPER HALF BLOCK
+        (false, true) => '▄', // U+2584 LOWER HALF BLOCK
+        (false, false) => OFF_PIXEL,
+    }
+}
+
+// Upper half blocks in the given (upper, lower) colors, only switching colors where they
+// change, then back to the terminal's own colors
+fn colored_line(cells: impl Iterator<Item = ([u8; 3], [u8; 3])>) -> String {
+    let mut line = String::new();
+    let mut last = None;
+    for (upper, lower) in cells {
+        if last != Some((upper, lower)) {
+            let ([r, g, b], [br, bg, bb]) = (upper, lower);
+            line += &format!("{}{}", Fg(Rgb(r, g, b)), Bg(Rgb(br, bg, bb)));
+            last = Some((upper, lower));
+        }
+
+        line.push('▀'); // U+2580 UPPER HALF BLOCK
+    }
+
+    line += &format!("{}{}", Fg(Reset), Bg(Reset));
+    line
+}
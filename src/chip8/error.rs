@@ -0,0 +1,62 @@
+use std::fmt;
+
+use super::stack::StackError;
+
+// Why the ROM can't go on running; reported along with the VM state
+#[derive(Clone, Debug, PartialEq)]
+pub enum VmError {
+    // Jumps below the ROM would run the interpreter's own memory as CHIP-8 code
+    JumpIntoInterpreter {
+        opcode: u16,
+        target: u16,
+    },
+    // Jumps past the end of memory can't fetch an instruction
+    JumpPastMemory {
+        opcode: u16,
+        target: u16,
+    },
+    // 0NNN calling a routine we can't (or, with --machine-code error, won't) emulate
+    MachineCode {
+        opcode: u16,
+        addr: u16,
+    },
+    // 2NNN with the stack full, or 00EE with it empty; calls is the chain of calls it was in
+    Stack {
+        opcode: u16,
+        error: StackError,
+        depth: usize,
+        calls: String,
+    },
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VmError::JumpIntoInterpreter { opcode, target } => write!(
+                f,
+                "0x{:04X}: Jump to 0x{:03X}, in the interpreter's reserved memory",
+                opcode, target
+            ),
+            VmError::JumpPastMemory { opcode, target } => write!(
+                f,
+                "0x{:04X}: Jump to 0x{:03X}, past the end of memory",
+                opcode, target
+            ),
+            VmError::MachineCode { opcode, addr } => write!(
+                f,
+                "0x{:04X}: Unsupported machine code routine at 0x{:03X}",
+                opcode, addr
+            ),
+            VmError::Stack {
+                opcode,
+                error,
+                depth,
+                calls,
+            } => write!(
+                f,
+                "0x{:04X}: {} (depth {}). Calls: {}",
+                opcode, error, depth, calls
+            ),
+        }
+    }
+}
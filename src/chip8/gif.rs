@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::display::Palette;
+
+const FRAMES_PER_SECOND: u32 = 60;
+
+// Most GIF viewers (browsers especially) treat delays below 2 centiseconds
+// as 10 centiseconds, so frames shorter than this are merged into the next one
+const MIN_DELAY_CS: u32 = 2;
+
+// Two-color images still need at least 2 bits per code (GIF spec minimum)
+const LZW_MIN_CODE_SIZE: u8 = 2;
+const LZW_MAX_CODES: u16 = 4096;
+
+// Records frames (sampled at 60Hz) into an animated GIF.
+// Identical consecutive frames are merged by extending the previous frame's delay,
+// so the file stays small while the ROM sits on a static screen.
+pub struct GifRecorder {
+    output: BufWriter<File>,
+    width: usize,
+    height: usize,
+    scale: usize,
+    pending_frame: Option<Vec<bool>>,
+    pending_start: u32, // Tick the pending frame first appeared on
+    tick: u32,
+}
+
+impl GifRecorder {
+    pub fn new(
+        path: &Path,
+        width: usize,
+        height: usize,
+        scale: usize,
+        palette: Palette,
+    ) -> Result<Self, std::io::Error> {
+        debug_assert!(scale > 0, "Invalid GIF scale!");
+        let mut output = BufWriter::new(File::create(path)?);
+
+        // Header and logical screen descriptor, with a 2-entry global color table
+        output.write_all(b"GIF89a")?;
+        output.write_all(&((width * scale) as u16).to_le_bytes())?;
+        output.write_all(&((height * scale) as u16).to_le_bytes())?;
+        output.write_all(&[0x80, 0, 0])?;
+        output.write_all(&palette.off)?;
+        output.write_all(&palette.on)?;
+
+        // NETSCAPE2.0 application extension: loop forever
+        output.write_all(&[0x21, 0xFF, 0x0B])?;
+        output.write_all(b"NETSCAPE2.0")?;
+        output.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+
+        Ok(GifRecorder {
+            output,
+            width,
+            height,
+            scale,
+            pending_frame: None,
+            pending_start: 0,
+            tick: 0,
+        })
+    }
+
+    // Call once per 60Hz tick with the current frame (row-major, true = pixel on)
+    pub fn capture(&mut self, frame: Vec<bool>) -> Result<(), std::io::Error> {
+        debug_assert!(
+            frame.len() == self.width * self.height,
+            "Invalid frame size!"
+        );
+        let tick = self.tick;
+        self.tick += 1;
+
+        match &self.pending_frame {
+            Some(pending) if *pending == frame => {}
+            Some(_) if Self::delay_cs(self.pending_start, tick) < MIN_DELAY_CS => {
+                // Pending frame was too short-lived to show; replace it
+                self.pending_frame = Some(frame);
+            }
+            Some(_) => {
+                self.flush_pending(tick)?;
+                self.pending_frame = Some(frame);
+                self.pending_start = tick;
+            }
+            None => {
+                self.pending_frame = Some(frame);
+                self.pending_start = tick;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Write out the last frame and the GIF trailer
+    pub fn finish(mut self) -> Result<(), std::io::Error> {
+        let end = self.tick.max(self.pending_start + 1);
+        self.flush_pending(end)?;
+        self.output.write_all(&[0x3B])?;
+        self.output.flush()
+    }
+
+    // Delay between two ticks, in centiseconds.
+    // Rounding cumulative time (rather than each frame) keeps the total length accurate:
+    // three 60Hz frames come out as 2cs, 2cs, 1cs instead of 2cs each.
+    fn delay_cs(start_tick: u32, end_tick: u32) -> u32 {
+        let to_cs = |tick: u32| (tick * 100 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND;
+        to_cs(end_tick) - to_cs(start_tick)
+    }
+
+    fn flush_pending(&mut self, end_tick: u32) -> Result<(), std::io::Error> {
+        let frame = match self.pending_frame.take() {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+
+        let delay = Self::delay_cs(self.pending_start, end_tick).max(MIN_DELAY_CS) as u16;
+
+        // Graphic control extension: no transparency, no disposal
+        self.output.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.output.write_all(&delay.to_le_bytes())?;
+        self.output.write_all(&[0x00, 0x00])?;
+
+        // Image descriptor covering the whole canvas, no local color table
+        let (width, height) = (self.width * self.scale, self.height * self.scale);
+        self.output.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.output.write_all(&(width as u16).to_le_bytes())?;
+        self.output.write_all(&(height as u16).to_le_bytes())?;
+        self.output.write_all(&[0x00])?;
+
+        // Scale up each pixel into a scale x scale block of color indices
+        let mut indices = Vec::with_capacity(width * height);
+        for row in frame.chunks(self.width) {
+            let mut scaled_row = Vec::with_capacity(width);
+            for &pixel in row {
+                scaled_row.extend(std::iter::repeat_n(pixel as u8, self.scale));
+            }
+
+            for _ in 0..self.scale {
+                indices.extend_from_slice(&scaled_row);
+            }
+        }
+
+        self.output.write_all(&[LZW_MIN_CODE_SIZE])?;
+        for block in lzw_encode(&indices, LZW_MIN_CODE_SIZE).chunks(255) {
+            self.output.write_all(&[block.len() as u8])?;
+            self.output.write_all(block)?;
+        }
+
+        // Block terminator
+        self.output.write_all(&[0x00])
+    }
+}
+
+// Variable-length-code LZW, as used by GIF: codes are packed LSB-first,
+// starting at (min_code_size + 1) bits and growing up to 12 bits,
+// with a clear code emitted whenever the code table fills up
+fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear_code: u16 = 1 << min_code_size;
+    let end_code = clear_code + 1;
+
+    let mut output = Vec::new();
+    let mut bit_buffer: u32 = 0;
+    let mut bit_count = 0;
+    let mut emit = |code: u16, code_size: u8, output: &mut Vec<u8>| {
+        bit_buffer |= (code as u32) << bit_count;
+        bit_count += code_size;
+        while bit_count >= 8 {
+            output.push(bit_buffer as u8);
+            bit_buffer >>= 8;
+            bit_count -= 8;
+        }
+    };
+
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end_code + 1;
+    let mut code_size = min_code_size + 1;
+    emit(clear_code, code_size, &mut output);
+
+    let mut indices = indices.iter();
+    let mut prefix = match indices.next() {
+        Some(&index) => index as u16,
+        None => {
+            emit(end_code, code_size, &mut output);
+            emit(0, 7, &mut output); // Flush remaining bits
+            return output;
+        }
+    };
+
+    for &index in indices {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+
+        emit(prefix, code_size, &mut output);
+        if next_code == LZW_MAX_CODES {
+            emit(clear_code, code_size, &mut output);
+            table.clear();
+            next_code = end_code + 1;
+            code_size = min_code_size + 1;
+        } else {
+            if next_code >= (1 << code_size) {
+                code_size += 1;
+            }
+
+            table.insert((prefix, index), next_code);
+            next_code += 1;
+        }
+
+        prefix = index as u16;
+    }
+
+    emit(prefix, code_size, &mut output);
+    emit(end_code, code_size, &mut output);
+    emit(0, 7, &mut output); // Flush remaining bits
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Plain GIF LZW decoder, to check the encoder against
+    fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear_code = 1u16 << min_code_size;
+        let end_code = clear_code + 1;
+        let mut bits = data
+            .iter()
+            .flat_map(|&byte| (0..8).map(move |bit| (byte >> bit) as u16 & 1));
+
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut code_size = min_code_size + 1;
+        let mut prev: Option<Vec<u8>> = None;
+        let mut output = Vec::new();
+        loop {
+            // Missing bits past the end of the data read as zeroes
+            let code = (0..code_size).fold(0, |code, bit| code | bits.next().unwrap_or(0) << bit);
+
+            if code == clear_code {
+                table = (0..clear_code + 2).map(|index| vec![index as u8]).collect();
+                code_size = min_code_size + 1;
+                prev = None;
+                continue;
+            }
+
+            if code == end_code {
+                return output;
+            }
+
+            let entry = match (table.get(code as usize), &prev) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(prev)) => [prev.as_slice(), &prev[..1]].concat(),
+                (None, None) => panic!("Invalid first code {}", code),
+            };
+
+            if let Some(prev) = prev {
+                table.push([prev.as_slice(), &entry[..1]].concat());
+                if table.len() == 1 << code_size && code_size < 12 {
+                    code_size += 1;
+                }
+            }
+
+            output.extend_from_slice(&entry);
+            prev = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_encodes_known_bytes() {
+        // Codes 4 (clear), 0, 6 (0,0), 0, 5 (end), 3 bits each, packed LSB-first
+        assert_eq!(lzw_encode(&[0, 0, 0, 0], 2), [0x84, 0x51]);
+    }
+
+    #[test]
+    fn lzw_round_trips_empty_input() {
+        assert_eq!(lzw_decode(&lzw_encode(&[], 2), 2), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn lzw_round_trips_past_full_code_table() {
+        // Enough noise to fill the 4096-entry table several times over
+        let mut state: u32 = 1;
+        let indices: Vec<u8> = (0..100_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8 & 1
+            })
+            .collect();
+
+        assert_eq!(lzw_decode(&lzw_encode(&indices, 2), 2), indices);
+    }
+
+    #[test]
+    fn gif_has_header_frame_and_trailer() {
+        let path = std::env::temp_dir().join(format!("chip8-test-{}.gif", std::process::id()));
+        let palette = Palette {
+            off: [0x11, 0x22, 0x33],
+            on: [0x44, 0x55, 0x66],
+        };
+
+        let mut recorder = GifRecorder::new(&path, 2, 1, 1, palette).unwrap();
+        recorder.capture(vec![true, false]).unwrap();
+        recorder.finish().unwrap();
+        let gif = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // Header, 2x1 logical screen, then the global color table
+        assert_eq!(&gif[..6], b"GIF89a");
+        assert_eq!(&gif[6..13], [2, 0, 1, 0, 0x80, 0, 0]);
+        assert_eq!(&gif[13..19], [0x11, 0x22, 0x33, 0x44, 0x55, 0x66]);
+
+        // The single frame's pixels, then the trailer
+        let image = gif.iter().position(|&byte| byte == 0x2C).unwrap();
+        let data_len = gif[image + 11] as usize;
+        let data = &gif[image + 12..image + 12 + data_len];
+        assert_eq!(lzw_decode(data, LZW_MIN_CODE_SIZE), [1, 0]);
+        assert_eq!(gif.last(), Some(&0x3B));
+    }
+}
@@ -0,0 +1,232 @@
+use super::display::Palette;
+
+// Kitty's graphics protocol limits each escape sequence to 4096 bytes of payload
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+// How frames are drawn in the terminal
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Renderer {
+    #[default]
+    Text, // Block characters; works everywhere
+    Sixel, // DEC Sixel graphics (xterm -ti vt340, foot, WezTerm, mlterm, ...)
+    Kitty, // Kitty graphics protocol (kitty, WezTerm, Konsole, ...)
+}
+
+impl std::str::FromStr for Renderer {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "text" => Ok(Renderer::Text),
+            "sixel" => Ok(Renderer::Sixel),
+            "kitty" => Ok(Renderer::Kitty),
+            _ => Err(format!(
+                "Unknown renderer '{}' (expected text, sixel or kitty)",
+                name
+            )),
+        }
+    }
+}
+
+// Sixel image of the frame, with each pixel scaled up to a square of the given size.
+// Each band of six rows is drawn once per color, run-length encoded.
+pub fn encode_sixel(
+    pixels: &[bool],
+    width: usize,
+    height: usize,
+    scale: usize,
+    palette: Palette,
+) -> String {
+    let (image_width, image_height) = (width * scale, height * scale);
+
+    // 1:1 pixel aspect ratio, then color registers in percent
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", image_width, image_height);
+    for (register, color) in [palette.off, palette.on].iter().enumerate() {
+        let [r, g, b] = color.map(|channel| channel as u32 * 100 / 255);
+        out += &format!("#{};2;{};{};{}", register, r, g, b);
+    }
+
+    let is_on = |x: usize, y: usize| pixels[(y / scale) * width + x / scale];
+    for band_top in (0..image_height).step_by(6) {
+        for (register, on) in [false, true].into_iter().enumerate() {
+            if register > 0 {
+                out.push('$'); // Back to the start of the band for the next color
+            }
+
+            out += &format!("#{}", register);
+
+            // Bit N of each sixel is row N of the band
+            let sixels = (0..image_width).map(|x| {
+                let bits = (0..6)
+                    .filter(|row| band_top + row < image_height)
+                    .filter(|row| is_on(x, band_top + row) == on)
+                    .fold(0, |bits, row| bits | (1 << row));
+                (0x3F + bits) as u8 as char
+            });
+
+            push_runs(&mut out, sixels);
+        }
+
+        out.push('-');
+    }
+
+    out += "\x1b\\";
+    out
+}
+
+// Sixel's run-length encoding: "!<count><sixel>" for runs longer than 3
+fn push_runs(out: &mut String, sixels: impl Iterator<Item = char>) {
+    let mut run: Option<(char, usize)> = None;
+    for sixel in sixels.map(Some).chain(std::iter::once(None)) {
+        match (run, sixel) {
+            (Some((run_sixel, count)), Some(sixel)) if run_sixel == sixel => {
+                run = Some((run_sixel, count + 1));
+                continue;
+            }
+            (Some((run_sixel, count)), _) if count > 3 => {
+                *out += &format!("!{}{}", count, run_sixel)
+            }
+            (Some((run_sixel, count)), _) => out.extend(std::iter::repeat_n(run_sixel, count)),
+            (None, _) => {}
+        }
+
+        run = sixel.map(|sixel| (sixel, 1));
+    }
+}
+
+// Kitty graphics protocol: transmit and display the frame as raw RGB, replacing the
+// previous frame (image 1). Replies are suppressed so they don't show up as key presses.
+pub fn encode_kitty(
+    pixels: &[bool],
+    width: usize,
+    height: usize,
+    scale: usize,
+    palette: Palette,
+) -> String {
+    let (image_width, image_height) = (width * scale, height * scale);
+    let mut rgb = Vec::with_capacity(image_width * image_height * 3);
+    for y in 0..image_height {
+        for x in 0..image_width {
+            let on = pixels[(y / scale) * width + x / scale];
+            rgb.extend_from_slice(if on { &palette.on } else { &palette.off });
+        }
+    }
+
+    let data = base64(&rgb);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+    let mut out = String::with_capacity(data.len() + chunks.len() * 16 + 64);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        if i == 0 {
+            // Don't move the cursor, so the image stays put
+            out += &format!(
+                "\x1b_Ga=T,f=24,s={},v={},i=1,p=1,q=2,C=1,m={};",
+                image_width, image_height, more
+            );
+        } else {
+            out += &format!("\x1b_Gm={};", more);
+        }
+
+        out += std::str::from_utf8(chunk).unwrap();
+        out += "\x1b\\";
+    }
+
+    out
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let bits = group.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+            bits | (byte as u32) << (16 - i * 8)
+        });
+
+        for i in 0..4 {
+            if i <= group.len() {
+                out.push(ALPHABET[(bits >> (18 - i * 6)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PALETTE: Palette = Palette {
+        off: [0x00, 0x00, 0x00],
+        on: [0xFF, 0x80, 0x00],
+    };
+
+    #[test]
+    fn sixel_bytes() {
+        // Band of one row: "?" is an empty sixel, "@" just the top row
+        assert_eq!(
+            encode_sixel(&[true, false], 2, 1, 1, PALETTE),
+            "\x1bP0;1;0q\"1;1;2;1#0;2;0;0;0#1;2;100;50;0#0?@$#1@?-\x1b\\"
+        );
+    }
+
+    #[test]
+    fn sixel_scales_and_run_length_encodes() {
+        // Each pixel becomes 2x2; runs of more than 3 sixels are compressed
+        assert_eq!(
+            encode_sixel(&[false, false, true, false], 4, 1, 2, PALETTE),
+            "\x1bP0;1;0q\"1;1;8;2#0;2;0;0;0#1;2;100;50;0#0!4B??BB$#1!4?BB??-\x1b\\"
+        );
+    }
+
+    #[test]
+    fn kitty_bytes() {
+        assert_eq!(
+            encode_kitty(&[true, false], 2, 1, 1, PALETTE),
+            "\x1b_Ga=T,f=24,s=2,v=1,i=1,p=1,q=2,C=1,m=0;/4AAAAAA\x1b\\"
+        );
+    }
+
+    #[test]
+    fn kitty_splits_payload_into_chunks() {
+        // 64x32 at scale 2 is 24576 bytes of RGB, or 32768 of base64: 8 chunks
+        let image = encode_kitty(&[false; 64 * 32], 64, 32, 2, PALETTE);
+        let chunks: Vec<&str> = image
+            .split("\x1b\\")
+            .filter(|chunk| !chunk.is_empty())
+            .collect();
+        assert_eq!(chunks.len(), 8);
+        assert!(chunks[0].starts_with("\x1b_Ga=T,f=24,s=128,v=64,i=1,p=1,q=2,C=1,m=1;"));
+        for chunk in &chunks[1..7] {
+            assert_eq!(
+                *chunk,
+                format!("\x1b_Gm=1;{}", "A".repeat(KITTY_CHUNK_SIZE))
+            );
+        }
+
+        assert_eq!(
+            chunks[7],
+            format!("\x1b_Gm=0;{}", "A".repeat(KITTY_CHUNK_SIZE))
+        );
+    }
+
+    #[test]
+    fn base64_vectors() {
+        let cases = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(base64(input.as_bytes()), expected);
+        }
+    }
+}
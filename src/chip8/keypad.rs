@@ -4,68 +4,229 @@ use termion::input::TermRead;
 const NUM_KEYS: usize = 16;
 const KEY_TIMEOUT: u8 = 16; // Length of each key press in frames
 
-// Mapping of COSMAC VIP keyboard (0-F keys, represented by index) to QWERTY layout
-const KEYS: [Key; NUM_KEYS] = [
-    Key::Char('x'),
-    Key::Char('1'),
-    Key::Char('2'),
-    Key::Char('3'),
-    Key::Char('q'),
-    Key::Char('w'),
-    Key::Char('e'),
-    Key::Char('a'),
-    Key::Char('s'),
-    Key::Char('d'),
-    Key::Char('z'),
-    Key::Char('c'),
-    Key::Char('4'),
-    Key::Char('r'),
-    Key::Char('f'),
-    Key::Char('v'),
+// COSMAC VIP key (0-F) at each position of its 4x4 keyboard, read row by row
+#[rustfmt::skip]
+const VIP_LAYOUT: [usize; NUM_KEYS] = [
+    0x1, 0x2, 0x3, 0xC,
+    0x4, 0x5, 0x6, 0xD,
+    0x7, 0x8, 0x9, 0xE,
+    0xA, 0x0, 0xB, 0xF,
+];
+
+// The same block of keys on common keyboard layouts, read row by row
+const PRESETS: [(&str, &str); 4] = [
+    ("qwerty", "1234qwerasdfzxcv"),
+    ("azerty", "1234azerqsdfwxcv"),
+    ("dvorak", "1234',.paoeu;qjk"),
+    ("colemak", "1234qwfparstzxcv"),
+];
+
+// Mapping of COSMAC VIP keyboard (0-F keys, represented by index) to the host keyboard
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keymap {
+    keys: [Key; NUM_KEYS],
+    arrows: [Option<usize>; 4], // Keys pressed by up, down, left and right
+}
+
+impl Keymap {
+    // Preset name, or 16 characters giving the host key for each position of the
+    // VIP keyboard, row by row (e.g. "1234qwerasdfzxcv" for QWERTY)
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let layout = PRESETS
+            .iter()
+            .find(|(name, _)| *name == text)
+            .map_or(text, |(_, layout)| *layout);
+
+        let chars: Vec<char> = layout.chars().collect();
+        if chars.len() != NUM_KEYS {
+            return Err(format!(
+                "Keymap '{}' should be a preset (qwerty, azerty, dvorak, colemak) \
+                or 16 keys, row by row",
+                text
+            ));
+        }
+
+        let mut keys = [Key::Null; NUM_KEYS];
+        for (position, &key_char) in chars.iter().enumerate() {
+            if chars[..position].contains(&key_char) {
+                return Err(format!("Keymap '{}' uses '{}' twice", text, key_char));
+            }
+
+            keys[VIP_LAYOUT[position]] = Key::Char(key_char);
+        }
+
+        Ok(Keymap {
+            keys,
+            arrows: [None; 4],
+        })
+    }
+
+    // Also press these keys (up, down, left, right) with the arrow keys
+    pub fn set_arrows(&mut self, arrows: [u8; 4]) {
+        for (arrow, key) in self.arrows.iter_mut().zip(arrows) {
+            *arrow = Some(key as usize);
+        }
+    }
+
+    fn key_index(&self, key: Key) -> Option<usize> {
+        let arrow = match key {
+            Key::Up => self.arrows[0],
+            Key::Down => self.arrows[1],
+            Key::Left => self.arrows[2],
+            Key::Right => self.arrows[3],
+            _ => None,
+        };
+
+        arrow.or_else(|| self.keys.iter().position(|&valid_key| key == valid_key))
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::parse(PRESETS[0].0).unwrap()
+    }
+}
+
+// Emulator controls, as opposed to keys passed through to the ROM
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hotkey {
+    ToggleRecording, // Ctrl-G
+    ToggleMute,      // Ctrl-S
+    ToggleTurbo,     // Ctrl-T
+    TogglePause,     // Ctrl-P
+    StepFrame,       // Ctrl-N
+    Reset,           // Ctrl-R
+    Reload,          // Ctrl-L
+}
+
+// Shown in the command-line help
+pub const HOTKEY_HELP: [(&str, &str); 8] = [
+    ("Ctrl-C", "Quit"),
+    ("Ctrl-G", "Start/stop GIF recording"),
+    ("Ctrl-S", "Mute/unmute"),
+    ("Ctrl-T", "Turbo: run as fast as possible"),
+    ("Ctrl-P", "Pause/resume"),
+    ("Ctrl-N", "Run one frame while paused"),
+    ("Ctrl-R", "Reset"),
+    ("Ctrl-L", "Reload the ROM from disk, then reset"),
 ];
 
 pub struct Keypad {
-    input: termion::input::Keys<termion::AsyncReader>,
+    keymap: Keymap,
+    input: Option<termion::input::Keys<termion::AsyncReader>>, // None in headless mode
     key_states: [u8; NUM_KEYS],
     queued_key: Option<u8>,
     waiting_for_input: bool,
     got_sigint: bool,
+    hotkey: Option<Hotkey>,
+    frame_presses: u16,    // Bit N set if key N was pressed this frame
+    ignore_rom_keys: bool, // Set while an input movie is playing
 }
 
 impl Keypad {
-    pub fn new() -> Self {
+    pub fn new(keymap: Keymap) -> Self {
         Keypad {
-            input: termion::async_stdin().keys(),
+            input: Some(termion::async_stdin().keys()),
+            ..Keypad::headless(keymap)
+        }
+    }
+
+    // Never reads stdin; no keys are ever pressed
+    pub fn headless(keymap: Keymap) -> Self {
+        Keypad {
+            keymap,
+            input: None,
             key_states: [0; NUM_KEYS],
             queued_key: None,
             waiting_for_input: false,
             got_sigint: false,
+            hotkey: None,
+            frame_presses: 0,
+            ignore_rom_keys: false,
         }
     }
 
     pub fn cycle(&mut self) {
-        self.decrement_key_timers();
-        let input = self.input.next();
+        let input = self.input.as_mut().and_then(|input| input.next());
 
         if let Some(Ok(next_key)) = input {
             match next_key {
                 Key::Ctrl('c') => {
                     self.got_sigint = true;
                 }
+                Key::Ctrl('g') => {
+                    self.hotkey = Some(Hotkey::ToggleRecording);
+                }
+                Key::Ctrl('s') => {
+                    self.hotkey = Some(Hotkey::ToggleMute);
+                }
+                Key::Ctrl('t') => {
+                    self.hotkey = Some(Hotkey::ToggleTurbo);
+                }
+                Key::Ctrl('p') => {
+                    self.hotkey = Some(Hotkey::TogglePause);
+                }
+                Key::Ctrl('n') => {
+                    self.hotkey = Some(Hotkey::StepFrame);
+                }
+                Key::Ctrl('r') => {
+                    self.hotkey = Some(Hotkey::Reset);
+                }
+                Key::Ctrl('l') => {
+                    self.hotkey = Some(Hotkey::Reload);
+                }
+                _ if self.ignore_rom_keys => {}
                 _ => {
-                    if let Some(key_ind) = KEYS.iter().position(|&valid_key| next_key == valid_key)
-                    {
-                        if self.key_states[key_ind] == 0 {
-                            self.key_states[key_ind] = KEY_TIMEOUT;
-                        }
-
-                        self.waiting_for_input = false;
+                    if let Some(key_ind) = self.keymap.key_index(next_key) {
+                        self.press(key_ind);
                     }
                 }
             }
         }
     }
 
+    pub fn press(&mut self, key_ind: usize) {
+        debug_assert!(key_ind < NUM_KEYS, "Invalid keypad value!");
+        if self.key_states[key_ind] == 0 {
+            self.key_states[key_ind] = KEY_TIMEOUT;
+        }
+
+        self.frame_presses |= 1 << key_ind;
+        self.waiting_for_input = false;
+    }
+
+    // Press every key set in the mask (bit N = key N), e.g. from an input movie
+    pub fn press_mask(&mut self, mask: u16) {
+        for key_ind in 0..NUM_KEYS {
+            if mask & (1 << key_ind) != 0 {
+                self.press(key_ind);
+            }
+        }
+    }
+
+    pub fn frame_presses(&self) -> u16 {
+        self.frame_presses
+    }
+
+    // Called after each emulated frame, so keys are held for the same number of frames
+    // however many of them run per frame of real time
+    pub fn end_frame(&mut self) {
+        self.decrement_key_timers();
+        self.frame_presses = 0;
+    }
+
+    // Let go of every key, as at power-on
+    pub fn release_all(&mut self) {
+        self.key_states = [0; NUM_KEYS];
+        self.queued_key = None;
+        self.waiting_for_input = false;
+        self.frame_presses = 0;
+    }
+
+    pub fn set_ignore_rom_keys(&mut self, ignore: bool) {
+        self.ignore_rom_keys = ignore;
+    }
+
     pub fn get_input(&mut self) -> Option<u8> {
         // First, wait for a key to be pressed
         if self.queued_key.is_none() {
@@ -97,14 +258,20 @@ impl Keypad {
         self.key_states[key_val] != 0
     }
 
+    // Ctrl-C, or a termination signal
     pub fn got_sigint(&self) -> bool {
-        self.got_sigint
+        self.got_sigint || super::shutdown::received_signal().is_some()
+    }
+
+    // Hotkey pressed this frame, if any; only reported once
+    pub fn take_hotkey(&mut self) -> Option<Hotkey> {
+        self.hotkey.take()
     }
 
     // Used only in debug mode; not for normal ROM input
     #[cfg(debug_assertions)]
     pub fn read_stdin(&mut self) -> Option<Result<termion::event::Key, std::io::Error>> {
-        self.input.next()
+        self.input.as_mut().and_then(|input| input.next())
     }
 
     fn decrement_key_timers(&mut self) {
@@ -115,3 +282,86 @@ impl Keypad {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_held_for_a_number_of_frames() {
+        let mut keypad = Keypad::headless(Keymap::default());
+        keypad.press(0xA);
+        for _ in 0..KEY_TIMEOUT {
+            assert!(keypad.is_key_pressed(0xA));
+            keypad.end_frame();
+        }
+
+        assert!(!keypad.is_key_pressed(0xA));
+    }
+
+    #[test]
+    fn presses_add_up_until_the_frame_runs() {
+        // While paused, no frames run: keys stay down, and every key pressed meanwhile
+        // is recorded for the frame that finally runs
+        let mut keypad = Keypad::headless(Keymap::default());
+        keypad.press(0x1);
+        keypad.cycle();
+        keypad.press(0xF);
+        keypad.cycle();
+        assert_eq!(keypad.frame_presses(), 0x8002);
+        assert!(keypad.is_key_pressed(0x1) && keypad.is_key_pressed(0xF));
+
+        keypad.end_frame();
+        assert_eq!(keypad.frame_presses(), 0);
+        assert!(keypad.is_key_pressed(0x1));
+    }
+
+    #[test]
+    fn replayed_presses_give_the_same_key_states() {
+        let frames = [0x0001, 0x0000, 0x0001, 0x0010, 0x0000];
+        let mut live = Keypad::headless(Keymap::default());
+        let mut replay = Keypad::headless(Keymap::default());
+        for mask in frames {
+            for key_ind in (0..NUM_KEYS).filter(|key_ind| mask & (1 << key_ind) != 0) {
+                live.press(key_ind);
+            }
+
+            replay.press_mask(live.frame_presses());
+            assert_eq!(live.key_states, replay.key_states);
+            live.end_frame();
+            replay.end_frame();
+        }
+    }
+
+    #[test]
+    fn release_all_starts_over() {
+        let mut keypad = Keypad::headless(Keymap::default());
+        keypad.press(0x3);
+        assert_eq!(keypad.get_input(), None);
+        keypad.release_all();
+        assert_eq!(keypad.frame_presses(), 0);
+        assert!(!keypad.is_key_pressed(0x3));
+
+        // Doesn't hand back the key that was waiting to be released
+        keypad.press(0x4);
+        assert_eq!(keypad.get_input(), None);
+        for _ in 0..KEY_TIMEOUT {
+            keypad.end_frame();
+        }
+
+        assert_eq!(keypad.get_input(), Some(0x4));
+    }
+
+    #[test]
+    fn waits_for_a_key_to_be_released() {
+        let mut keypad = Keypad::headless(Keymap::default());
+        assert_eq!(keypad.get_input(), None);
+        keypad.press(0x5);
+        assert_eq!(keypad.get_input(), None);
+        for _ in 0..KEY_TIMEOUT {
+            keypad.end_frame();
+        }
+
+        assert_eq!(keypad.get_input(), Some(0x5));
+    }
+}
@@ -0,0 +1,88 @@
+use super::stack::VIP_STACK_TOP;
+
+// Where the stack and screen live
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MemoryLayout {
+    #[default]
+    Separate, // Outside the 4KB of RAM, out of the ROM's reach
+    Vip, // At the top of RAM, as on the COSMAC VIP, where ROMs can read and write them
+}
+
+impl std::str::FromStr for MemoryLayout {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "separate" => Ok(MemoryLayout::Separate),
+            "vip" => Ok(MemoryLayout::Vip),
+            _ => Err(format!(
+                "Unknown memory layout '{}' (expected separate or vip)",
+                name
+            )),
+        }
+    }
+}
+
+// Parses back with from_str
+impl std::fmt::Display for MemoryLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            MemoryLayout::Separate => "separate",
+            MemoryLayout::Vip => "vip",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl MemoryLayout {
+    // End of the RAM a ROM can use. The VIP interpreter keeps everything from 0xEA0 up for
+    // itself (48 bytes of stack, its variables, then the screen); stacks deeper than that
+    // reach further down.
+    pub fn ram_end(self, stack_depth: usize) -> usize {
+        match self {
+            MemoryLayout::Separate => super::MEM_SIZE,
+            MemoryLayout::Vip => {
+                VIP_RESERVED_ADDR.min(VIP_STACK_TOP.saturating_sub(2 * stack_depth))
+            }
+        }
+    }
+}
+
+const VIP_RESERVED_ADDR: usize = 0xEA0;
+
+// With the VIP layout, the screen is 0xF00-0xFFF: 8 bytes per row, leftmost pixel in the
+// high bit. The stack is just below the interpreter's work area at 0xED0.
+pub const VIP_DISPLAY_ADDR: usize = 0xF00;
+
+pub fn vip_pixel(mem: &[u8], row: usize, col: usize) -> bool {
+    mem[VIP_DISPLAY_ADDR + row * super::DISPLAY_WIDTH / 8 + col / 8] & (0x80 >> (col % 8)) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ram_end() {
+        assert_eq!(MemoryLayout::Separate.ram_end(12), crate::chip8::MEM_SIZE);
+        assert_eq!(MemoryLayout::Vip.ram_end(12), 0xEA0);
+        assert_eq!(MemoryLayout::Vip.ram_end(24), 0xEA0);
+        assert_eq!(MemoryLayout::Vip.ram_end(40), 0xE80);
+        assert_eq!(MemoryLayout::Vip.ram_end(usize::MAX / 2), 0);
+    }
+
+    #[test]
+    fn vip_pixels_are_bits_of_the_display_page() {
+        let mut mem = [0; crate::chip8::MEM_SIZE];
+        mem[0xF00] = 0x80; // (0, 0)
+        mem[0xF11] = 0x01; // (2, 15)
+        mem[0xFFF] = 0x01; // (31, 63)
+
+        let lit: Vec<(usize, usize)> = (0..32)
+            .flat_map(|row| (0..64).map(move |col| (row, col)))
+            .filter(|&(row, col)| vip_pixel(&mem, row, col))
+            .collect();
+        assert_eq!(lit, [(0, 0), (2, 15), (31, 63)]);
+    }
+}
@@ -0,0 +1,188 @@
+// What 0NNN (run the 1802 machine code routine at NNN) does
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MachineCode {
+    Ignore, // Carry on with the next instruction
+    Error,  // Stop the emulator
+    #[default]
+    Emulate, // Run routines we recognize, and stop on any others
+}
+
+impl std::str::FromStr for MachineCode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "ignore" => Ok(MachineCode::Ignore),
+            "error" => Ok(MachineCode::Error),
+            "emulate" => Ok(MachineCode::Emulate),
+            _ => Err(format!(
+                "Unknown machine code policy '{}' (expected ignore, error or emulate)",
+                name
+            )),
+        }
+    }
+}
+
+// Parses back with from_str
+impl std::fmt::Display for MachineCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            MachineCode::Ignore => "ignore",
+            MachineCode::Error => "error",
+            MachineCode::Emulate => "emulate",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+// Routines that can be run without emulating an 1802
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Routine {
+    Return,           // Does nothing; hands straight back to the interpreter
+    ClearScreen,      // Zeroes the display page
+    WaitForInterrupt, // Idles until the next 60Hz display interrupt
+}
+
+// Routines recognized by their code, as hex bytes; "??" matches any byte, for branch
+// targets that depend on where the routine is. 0NNN runs code with R3 as the program
+// counter and returns with SEP R4; RB.1 holds the display page. The interpreter's own
+// clear-screen and return routines need no entry: 00E0 and 00EE are decoded as
+// instructions before getting here.
+const ROUTINE_CODE: [(&str, Routine); 3] = [
+    // SEP R4: back to the interpreter
+    ("D4", Routine::Return),
+    // GHI RB, PHI RF, LDI 00, PLO RF: RF = start of the display page
+    // loop: LDI 00, STR RF, INC RF, GLO RF, BNZ loop: zero it to the end of the page
+    // SEP R4
+    (
+        "9B BF F8 00 AF F8 00 5F 1F 8F 3A ?? D4",
+        Routine::ClearScreen,
+    ),
+    // IDL, SEP R4: the display interrupt wakes the CPU up
+    ("00 D4", Routine::WaitForInterrupt),
+];
+
+pub fn recognize(mem: &[u8], addr: u16) -> Option<Routine> {
+    ROUTINE_CODE
+        .iter()
+        .find(|(code, _)| matches(&mem[addr as usize..], code))
+        .map(|(_, routine)| *routine)
+}
+
+fn matches(mem: &[u8], code: &str) -> bool {
+    let mut bytes = mem.iter();
+    code.split_whitespace().all(|hex| match bytes.next() {
+        Some(&byte) => hex == "??" || u8::from_str_radix(hex, 16) == Ok(byte),
+        None => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::cdp1802::{Bus, Cpu};
+
+    const CLEAR_SCREEN: [u8; 13] = [
+        0x9B, 0xBF, 0xF8, 0x00, 0xAF, 0xF8, 0x00, 0x5F, 0x1F, 0x8F, 0x3A, 0x05, 0xD4,
+    ];
+
+    struct Ram(Vec<u8>);
+
+    impl Bus for Ram {
+        fn read(&mut self, addr: u16) -> u8 {
+            self.0[addr as usize]
+        }
+
+        fn write(&mut self, addr: u16, val: u8) {
+            self.0[addr as usize] = val;
+        }
+
+        fn output(&mut self, _port: u8, _val: u8) {}
+
+        fn input(&mut self, _port: u8) -> u8 {
+            0
+        }
+
+        fn flag(&self, _ef: u8) -> bool {
+            false
+        }
+    }
+
+    fn mem_with(addr: usize, code: &[u8]) -> Vec<u8> {
+        let mut mem = vec![0xFF; 0x1000];
+        mem[addr..addr + code.len()].copy_from_slice(code);
+        mem
+    }
+
+    #[test]
+    fn recognizes_routines() {
+        let mut clear_elsewhere = CLEAR_SCREEN;
+        clear_elsewhere[11] = 0x45;
+        for (code, routine) in [
+            (&[0xD4][..], Some(Routine::Return)),
+            (&CLEAR_SCREEN, Some(Routine::ClearScreen)),
+            (&clear_elsewhere, Some(Routine::ClearScreen)),
+            (&[0x00, 0xD4], Some(Routine::WaitForInterrupt)),
+            (&[0x00, 0xD5], None),
+            (&[0x9B, 0xBF, 0xF8, 0x01], None),
+        ] {
+            assert_eq!(
+                recognize(&mem_with(0x300, code), 0x300),
+                routine,
+                "{:X?}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn stops_matching_at_the_end_of_memory() {
+        let mem = mem_with(0xFFF, &[0x00]);
+        assert_eq!(recognize(&mem, 0xFFF), None);
+    }
+
+    // Run the routine at 0x240 on an 1802 set up the way the VIP interpreter calls it,
+    // until it hands back with SEP R4
+    fn run_on_1802(mem: Vec<u8>) -> (Cpu, Ram) {
+        let mut ram = Ram(mem);
+        let mut cpu = Cpu::new();
+        cpu.p = 3;
+        cpu.x = 2;
+        cpu.r[2] = 0xECF;
+        cpu.r[3] = 0x240;
+        cpu.r[0xB] = 0x0F00;
+        for _ in 0..10_000 {
+            if cpu.p == 4 || cpu.idle {
+                break;
+            }
+            cpu.step(&mut ram);
+        }
+
+        (cpu, ram)
+    }
+
+    #[test]
+    fn clear_screen_code_clears_the_display_page() {
+        let mut clear = CLEAR_SCREEN;
+        clear[11] = 0x45;
+        let mut mem = mem_with(0x240, &clear);
+        mem[0xF00..].fill(0xAA);
+
+        let (cpu, ram) = run_on_1802(mem);
+        assert_eq!(cpu.p, 4);
+        assert!(ram.0[0xF00..].iter().all(|&byte| byte == 0));
+        assert!(ram.0[0xE00..0xF00].iter().all(|&byte| byte == 0xFF));
+    }
+
+    #[test]
+    fn wait_code_idles_until_an_interrupt() {
+        let (mut cpu, mut ram) = run_on_1802(mem_with(0x240, &[0x00, 0xD4]));
+        assert!(cpu.idle);
+
+        // The interrupt handler returns to where the CPU idled
+        cpu.idle = false;
+        cpu.step(&mut ram);
+        assert_eq!(cpu.p, 4);
+    }
+}
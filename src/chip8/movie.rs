@@ -0,0 +1,260 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use super::quirks::Quirks;
+use super::Options;
+
+// Input movies are plain text, so they can be inspected (or hand-edited) easily:
+//
+//   chip8-movie 2
+//   rom-sha1 <hex digest of the ROM>
+//   seed <initial RNG seed>
+//   quirks <enabled quirks, as accepted by --quirks>
+//   platform <as accepted by --platform>
+//   cpu-hz, timing, stack-depth, memory, machine-code <likewise>
+//   <one line per frame: hex mask of keys pressed that frame (bit N = key N)>
+//
+// Only key presses are stored, not key states: releases are simulated by the keypad's
+// timers, so replaying the same presses on the same frames reproduces the same states.
+// Version 1 movies stop after the quirks; they're replayed with the current settings.
+const MAGIC: &str = "chip8-movie 2";
+const MAGIC_V1: &str = "chip8-movie 1";
+
+// Options that change how the ROM runs, besides the seed and quirks, in header order
+const SETTINGS: [&str; 6] = [
+    "platform",
+    "cpu-hz",
+    "timing",
+    "stack-depth",
+    "memory",
+    "machine-code",
+];
+
+pub struct Movie {
+    pub rom_hash: String,
+    pub seed: u8,
+    pub quirks: Quirks,
+    pub settings: Vec<(String, String)>, // Option names and values, to replay it with
+    frames: Vec<u16>,
+    next_frame: usize,
+}
+
+impl Movie {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|err| format!("Cannot open movie: {}", err))?;
+        let lines: Vec<String> = BufReader::new(file)
+            .lines()
+            .collect::<Result<_, _>>()
+            .map_err(|err| format!("Cannot read movie: {}", err))?;
+
+        let header_len = match lines.first().map(String::as_str) {
+            Some(MAGIC) => 4 + SETTINGS.len(),
+            Some(MAGIC_V1) => 4,
+            _ => return Err(String::from("Not a CHIP-8 input movie")),
+        };
+
+        if lines.len() < header_len {
+            return Err(String::from("Movie header is incomplete"));
+        }
+
+        let rom_hash = header_value(&lines[1], "rom-sha1")?;
+        let seed = header_value(&lines[2], "seed")?
+            .parse()
+            .map_err(|_| String::from("Invalid seed in movie"))?;
+        let quirks = Quirks::parse(&header_value(&lines[3], "quirks")?)?;
+        let settings = lines[4..header_len]
+            .iter()
+            .zip(SETTINGS)
+            .map(|(line, key)| Ok((key.to_string(), header_value(line, key)?)))
+            .collect::<Result<_, String>>()?;
+
+        let mut frames = Vec::new();
+        for line in &lines[header_len..] {
+            let mask = u16::from_str_radix(line.trim(), 16)
+                .map_err(|_| format!("Invalid frame {} in movie", frames.len()))?;
+            frames.push(mask);
+        }
+
+        Ok(Movie {
+            rom_hash,
+            seed,
+            quirks,
+            settings,
+            frames,
+            next_frame: 0,
+        })
+    }
+
+    // Start again from the first frame
+    pub fn rewind(&mut self) {
+        self.next_frame = 0;
+    }
+
+    // Stop the movie after this many frames
+    pub fn truncate(&mut self, frames: usize) {
+        self.frames.truncate(frames);
+    }
+
+    // Keys pressed on the next frame, or None once the movie is over
+    pub fn next_frame(&mut self) -> Option<u16> {
+        let mask = self.frames.get(self.next_frame).copied();
+        self.next_frame += 1;
+        mask
+    }
+}
+
+fn header_value(line: &str, key: &str) -> Result<String, String> {
+    match line.split_once(' ') {
+        Some((line_key, value)) if line_key == key => Ok(value.trim().to_string()),
+        _ => Err(format!("Expected '{}' in movie header", key)),
+    }
+}
+
+pub struct MovieRecorder {
+    output: BufWriter<File>,
+}
+
+impl MovieRecorder {
+    pub fn new(
+        path: &Path,
+        rom_hash: &str,
+        seed: u8,
+        options: &Options,
+    ) -> Result<Self, std::io::Error> {
+        let values = [
+            options.platform.to_string(),
+            options.cpu_hz.to_string(),
+            options.timing.to_string(),
+            options.stack_depth().to_string(),
+            options.memory_layout.to_string(),
+            options.machine_code.to_string(),
+        ];
+
+        let mut output = BufWriter::new(File::create(path)?);
+        writeln!(output, "{}", MAGIC)?;
+        writeln!(output, "rom-sha1 {}", rom_hash)?;
+        writeln!(output, "seed {}", seed)?;
+        writeln!(output, "quirks {}", options.quirks)?;
+        for (key, value) in SETTINGS.iter().zip(values) {
+            writeln!(output, "{} {}", key, value)?;
+        }
+
+        Ok(MovieRecorder { output })
+    }
+
+    pub fn record_frame(&mut self, key_presses: u16) -> Result<(), std::io::Error> {
+        writeln!(self.output, "{:04x}", key_presses)
+    }
+
+    pub fn finish(mut self) -> Result<(), std::io::Error> {
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::{MemoryLayout, Platform, Timing};
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("chip8-{}-{}.movie", name, std::process::id()))
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_path("round-trip");
+        let options = Options {
+            platform: Platform::Chip8X,
+            quirks: Quirks::parse("shift,jump").unwrap(),
+            cpu_hz: 1200,
+            timing: Timing::Vip,
+            stack_depth: Some(16),
+            memory_layout: MemoryLayout::Vip,
+            ..Default::default()
+        };
+
+        let mut recorder = MovieRecorder::new(&path, "abc123", 42, &options).unwrap();
+        for mask in [0x0000, 0x8001, 0x0010] {
+            recorder.record_frame(mask).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        let mut movie = Movie::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(movie.rom_hash, "abc123");
+        assert_eq!(movie.seed, 42);
+        assert_eq!(movie.quirks, options.quirks);
+
+        let settings: Vec<(&str, &str)> = movie
+            .settings
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            settings,
+            [
+                ("platform", "chip8x"),
+                ("cpu-hz", "1200"),
+                ("timing", "vip"),
+                ("stack-depth", "16"),
+                ("memory", "vip"),
+                ("machine-code", "emulate"),
+            ]
+        );
+
+        assert_eq!(movie.next_frame(), Some(0x0000));
+        assert_eq!(movie.next_frame(), Some(0x8001));
+        assert_eq!(movie.next_frame(), Some(0x0010));
+        assert_eq!(movie.next_frame(), None);
+        movie.rewind();
+        assert_eq!(movie.next_frame(), Some(0x0000));
+    }
+
+    #[test]
+    fn loads_version_1() {
+        let path = temp_path("version-1");
+        std::fs::write(
+            &path,
+            "chip8-movie 1\nrom-sha1 abc123\nseed 7\nquirks shift\n0001\n0002\n",
+        )
+        .unwrap();
+
+        let mut movie = Movie::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(movie.seed, 7);
+        assert!(movie.settings.is_empty());
+        assert_eq!(movie.next_frame(), Some(0x0001));
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let path = temp_path("bad-header");
+        let cases = [
+            ("not a movie\n", "Not a CHIP-8 input movie"),
+            (
+                "chip8-movie 2\nrom-sha1 abc\nseed 1\nquirks none\n",
+                "Movie header is incomplete",
+            ),
+            (
+                "chip8-movie 1\nrom-sha1 abc\nseed 300\nquirks none\n",
+                "Invalid seed in movie",
+            ),
+            (
+                "chip8-movie 1\nrom-sha1 abc\nquirks none\nseed 1\n",
+                "Expected 'seed' in movie header",
+            ),
+            (
+                "chip8-movie 1\nrom-sha1 abc\nseed 1\nquirks none\nxyz\n",
+                "Invalid frame 0 in movie",
+            ),
+        ];
+
+        for (text, error) in cases {
+            std::fs::write(&path, text).unwrap();
+            assert_eq!(Movie::load(&path).err().as_deref(), Some(error), "{}", text);
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
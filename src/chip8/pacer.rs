@@ -0,0 +1,111 @@
+use std::time::{Duration, Instant};
+
+// Sleeping is only accurate to a millisecond or so, so the end of each wait is a busy loop
+const SPIN_TIME: Duration = Duration::from_micros(1500);
+
+// Further behind than this, the emulator gives up on catching up and drops the frames
+const MAX_LAG: Duration = Duration::from_millis(250);
+
+// Rendering is skipped while behind schedule, but never for more frames than this in a row
+const MAX_SKIPPED_DRAWS: u32 = 4;
+
+// Schedules frames against absolute deadlines, so time lost oversleeping one frame is made
+// up on the next ones instead of adding up
+pub struct Pacer {
+    frame_length: Duration,
+    deadline: Instant,  // When the current frame should start
+    skipped_draws: u32, // In a row
+    pub stats: PacingStats,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PacingStats {
+    pub frames: u64,
+    pub late_frames: u64, // Started more than a millisecond after their deadline
+    pub total_lateness: Duration,
+    pub max_lateness: Duration,
+    pub skipped_draws: u64,
+    pub dropped_frames: u64, // Given up on after falling too far behind
+}
+
+impl Pacer {
+    pub fn new(frame_length: Duration) -> Self {
+        Pacer {
+            frame_length,
+            deadline: Instant::now(),
+            skipped_draws: 0,
+            stats: PacingStats::default(),
+        }
+    }
+
+    // Wait for the next frame's deadline
+    pub fn wait(&mut self) {
+        self.deadline += self.frame_length;
+
+        let now = Instant::now();
+        if now > self.deadline + MAX_LAG {
+            let behind = now - self.deadline;
+            self.stats.dropped_frames += (behind.as_nanos() / self.frame_length.as_nanos()) as u64;
+            self.deadline = now;
+        }
+
+        if let Some(remaining) = self.deadline.checked_duration_since(now) {
+            if remaining > SPIN_TIME {
+                std::thread::sleep(remaining - SPIN_TIME);
+            }
+
+            while Instant::now() < self.deadline {
+                std::hint::spin_loop();
+            }
+        }
+
+        let lateness = Instant::now().saturating_duration_since(self.deadline);
+        self.stats.frames += 1;
+        self.stats.total_lateness += lateness;
+        self.stats.max_lateness = self.stats.max_lateness.max(lateness);
+        if lateness > Duration::from_millis(1) {
+            self.stats.late_frames += 1;
+        }
+    }
+
+    // Start the schedule over from now, e.g. after running uncapped in turbo mode
+    pub fn resync(&mut self) {
+        self.deadline = Instant::now();
+    }
+
+    // Whether to draw this frame: under load, drawing is what gets dropped, so the ROM's
+    // timers keep running at full speed
+    pub fn should_draw(&mut self) -> bool {
+        let behind = Instant::now() > self.deadline + self.frame_length;
+        if behind && self.skipped_draws < MAX_SKIPPED_DRAWS {
+            self.skipped_draws += 1;
+            self.stats.skipped_draws += 1;
+            return false;
+        }
+
+        self.skipped_draws = 0;
+        true
+    }
+}
+
+impl PacingStats {
+    pub fn average_lateness(&self) -> Duration {
+        match self.frames {
+            0 => Duration::ZERO,
+            frames => Duration::from_secs_f64(self.total_lateness.as_secs_f64() / frames as f64),
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} frames, {:.2} ms late on average (worst {:.2} ms), {} late, \
+            {} draws skipped, {} frames dropped",
+            self.frames,
+            self.average_lateness().as_secs_f64() * 1000.,
+            self.max_lateness.as_secs_f64() * 1000.,
+            self.late_frames,
+            self.skipped_draws,
+            self.dropped_frames
+        )
+    }
+}
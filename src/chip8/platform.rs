@@ -0,0 +1,148 @@
+use std::ops::RangeInclusive;
+
+// CHIP-8 variants that ran on the COSMAC VIP, each with its own interpreter
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Platform {
+    #[default]
+    Chip8,
+    Hires,  // Two-page CHIP-8, with a 64x64 screen
+    Chip8X, // RCA's CHIP-8X, for the VP-590 color board and its second keypad
+}
+
+impl std::str::FromStr for Platform {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "chip8" => Ok(Platform::Chip8),
+            "hires" => Ok(Platform::Hires),
+            "chip8x" => Ok(Platform::Chip8X),
+            _ => Err(format!(
+                "Unknown platform '{}' (expected chip8, hires or chip8x)",
+                name
+            )),
+        }
+    }
+}
+
+// Parses back with from_str
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Platform::Chip8 => "chip8",
+            Platform::Hires => "hires",
+            Platform::Chip8X => "chip8x",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl Platform {
+    // Where ROMs are loaded; the CHIP-8X interpreter takes up an extra 256 bytes
+    pub fn rom_start(self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::Hires => 0x200,
+            Platform::Chip8X => 0x300,
+        }
+    }
+
+    // HIRES ROMs begin with 1260, a jump into the 1802 code they carry to patch the
+    // interpreter for the bigger screen. The program itself starts after it.
+    pub fn start_addr(self) -> usize {
+        match self {
+            Platform::Hires => 0x2C0,
+            _ => self.rom_start(),
+        }
+    }
+
+    pub fn max_rom_size(self) -> usize {
+        super::MEM_SIZE - self.rom_start()
+    }
+
+    // Every platform here ran on the VIP, whose interpreters keep 12 return addresses
+    pub fn stack_depth(self) -> usize {
+        super::stack::VIP_DEPTH
+    }
+
+    pub fn display_height(self) -> usize {
+        match self {
+            Platform::Hires => 64,
+            _ => 32,
+        }
+    }
+}
+
+// The VP-590's colors, in the order CHIP-8X numbers them
+const COLORS: [[u8; 3]; 8] = [
+    [0x00, 0x00, 0x00], // Black
+    [0xFF, 0x00, 0x00], // Red
+    [0x00, 0x00, 0xFF], // Blue
+    [0xFF, 0x00, 0xFF], // Violet
+    [0x00, 0xFF, 0x00], // Green
+    [0xFF, 0xFF, 0x00], // Yellow
+    [0x00, 0xFF, 0xFF], // Aqua
+    [0xFF, 0xFF, 0xFF], // White
+];
+
+// Background colors, in the order 02A0 steps through them
+const BACKGROUNDS: [[u8; 3]; 4] = [
+    [0x00, 0x00, 0x80], // Blue
+    [0x00, 0x00, 0x00], // Black
+    [0x00, 0x80, 0x00], // Green
+    [0x80, 0x00, 0x00], // Red
+];
+
+const STRIP_WIDTH: usize = 8;
+const STRIP_COLS: usize = 64 / STRIP_WIDTH;
+const STRIP_ROWS: usize = 32;
+pub const ZONE_HEIGHT: usize = 4; // Rows per zone for BXY0
+
+// VP-590 color board: one background color for the whole screen, and a foreground color
+// for each strip of 8x1 pixels. Starts out red on blue.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorBoard {
+    background: usize, // Index into BACKGROUNDS
+    foreground: [[u8; STRIP_COLS]; STRIP_ROWS],
+}
+
+impl ColorBoard {
+    pub fn new() -> Self {
+        ColorBoard {
+            background: 0,
+            foreground: [[1; STRIP_COLS]; STRIP_ROWS],
+        }
+    }
+
+    pub fn next_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUNDS.len();
+    }
+
+    // Color the strips in these columns (of 8 pixels) and rows (of 1 pixel); anything off
+    // the screen is ignored
+    pub fn set_foreground(
+        &mut self,
+        cols: RangeInclusive<usize>,
+        rows: RangeInclusive<usize>,
+        color: u8,
+    ) {
+        for row in self
+            .foreground
+            .iter_mut()
+            .take(rows.end() + 1)
+            .skip(*rows.start())
+        {
+            for strip in row.iter_mut().take(cols.end() + 1).skip(*cols.start()) {
+                *strip = color % COLORS.len() as u8;
+            }
+        }
+    }
+
+    pub fn color(&self, row: usize, col: usize, on: bool) -> [u8; 3] {
+        if on {
+            COLORS[self.foreground[row % STRIP_ROWS][col / STRIP_WIDTH] as usize]
+        } else {
+            BACKGROUNDS[self.background]
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+
+// Opcode behaviors that differ between the original CHIP-8 interpreter and later ones.
+// Each flag enables the original (COSMAC VIP) behavior, except wrap and jump, which come
+// from later interpreters like CHIP-48, SUPER-CHIP and XO-CHIP.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Quirks {
+    // 8XY6/8XYE: set Vx = Vy before shifting
+    pub shift: bool,
+    // FX55/FX65: increment I past the last register stored/loaded
+    pub load_store: bool,
+    // DXYN: wait for the next 60Hz display interrupt, ending the frame's instructions
+    pub display_wait: bool,
+    // DXYN: wrap sprites around the edges of the screen instead of clipping them
+    pub wrap: bool,
+    // BXNN: jump to XNN + Vx instead of NNN + V0
+    pub jump: bool,
+}
+
+type FlagAccessor = fn(&mut Quirks) -> &mut bool;
+
+// Name of each quirk, as used on the command line and in input movies
+const FLAGS: [(&str, FlagAccessor); 5] = [
+    ("shift", |quirks| &mut quirks.shift),
+    ("load-store", |quirks| &mut quirks.load_store),
+    ("display-wait", |quirks| &mut quirks.display_wait),
+    ("wrap", |quirks| &mut quirks.wrap),
+    ("jump", |quirks| &mut quirks.jump),
+];
+
+impl Quirks {
+    // The original CHIP-8 spec's shift and load/store. Display wait is left out, to keep
+    // legacy runs as they were before it existed; pick it with --quirks.
+    pub const LEGACY: Quirks = Quirks {
+        shift: true,
+        load_store: true,
+        display_wait: false,
+        wrap: false,
+        jump: false,
+    };
+
+    // The COSMAC VIP interpreter: legacy, plus waiting for the display interrupt on DXYN
+    pub const VIP: Quirks = Quirks {
+        display_wait: true,
+        ..Quirks::LEGACY
+    };
+
+    // Profile name if these quirks match one, otherwise the enabled quirks
+    pub fn profile(&self) -> String {
+        if *self == Quirks::LEGACY {
+            String::from("legacy")
+        } else if *self == Quirks::VIP {
+            String::from("vip")
+        } else if *self == Quirks::default() {
+            String::from("modern")
+        } else {
+            self.to_string()
+        }
+    }
+
+    // Profile name ("legacy", "vip", "modern"), or comma-separated quirk names to enable
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "legacy" => return Ok(Quirks::LEGACY),
+            "vip" => return Ok(Quirks::VIP),
+            "modern" | "none" => return Ok(Quirks::default()),
+            _ => {}
+        }
+
+        let mut quirks = Quirks::default();
+        for name in text.split(',') {
+            match FLAGS
+                .iter()
+                .find(|(flag_name, _)| *flag_name == name.trim())
+            {
+                Some((_, flag)) => *flag(&mut quirks) = true,
+                None => {
+                    let names: Vec<&str> = FLAGS.iter().map(|(name, _)| *name).collect();
+                    return Err(format!(
+                        "Unknown quirk '{}' (expected legacy, vip, modern, or any of: {})",
+                        name,
+                        names.join(", ")
+                    ));
+                }
+            }
+        }
+
+        Ok(quirks)
+    }
+}
+
+// Comma-separated names of enabled quirks, or "none"; parses back with Quirks::parse
+impl fmt::Display for Quirks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut quirks = *self;
+        let enabled: Vec<&str> = FLAGS
+            .iter()
+            .filter(|(_, flag)| *flag(&mut quirks))
+            .map(|(name, _)| *name)
+            .collect();
+
+        if enabled.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", enabled.join(","))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_profiles() {
+        assert_eq!(Quirks::parse("legacy"), Ok(Quirks::LEGACY));
+        assert_eq!(Quirks::parse("vip"), Ok(Quirks::VIP));
+        assert_eq!(Quirks::parse("modern"), Ok(Quirks::default()));
+        assert_eq!(Quirks::parse("none"), Ok(Quirks::default()));
+        assert_eq!(
+            Quirks::parse("shift,load-store,display-wait"),
+            Ok(Quirks::VIP)
+        );
+    }
+
+    #[test]
+    fn parses_quirk_lists() {
+        let quirks = Quirks::parse("wrap, jump").unwrap();
+        assert_eq!(
+            quirks,
+            Quirks {
+                wrap: true,
+                jump: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(Quirks::parse("shift,load-store"), Ok(Quirks::LEGACY));
+    }
+
+    #[test]
+    fn rejects_unknown_quirks() {
+        let error = Quirks::parse("shift,warp").unwrap_err();
+        assert!(error.starts_with("Unknown quirk 'warp'"), "{}", error);
+        assert!(Quirks::parse("").is_err());
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for quirks in [
+            Quirks::LEGACY,
+            Quirks::VIP,
+            Quirks::default(),
+            Quirks::parse("display-wait,wrap").unwrap(),
+        ] {
+            assert_eq!(Quirks::parse(&quirks.profile()), Ok(quirks));
+            assert_eq!(Quirks::parse(&quirks.to_string()), Ok(quirks));
+        }
+        assert_eq!(Quirks::VIP.profile(), "vip");
+        assert_eq!(Quirks::default().to_string(), "none");
+    }
+}
@@ -6,12 +6,23 @@ pub struct Rng {
 
 impl Rng {
     pub fn new() -> Self {
-        Rng {
-            seed: time::SystemTime::now()
+        Rng::with_seed(
+            time::SystemTime::now()
                 .duration_since(time::UNIX_EPOCH)
                 .unwrap()
                 .subsec_nanos() as u8,
-        }
+        )
+    }
+
+    // Fixed seed, for reproducible runs (e.g. input movies)
+    pub fn with_seed(seed: u8) -> Self {
+        // Xorshift never leaves 0, so nudge it to a usable state
+        Rng { seed: seed.max(1) }
+    }
+
+    // Current state; equal to the seed until the first byte is generated
+    pub fn seed(&self) -> u8 {
+        self.seed
     }
 
     // Cheap PRNG from George Marsaglia paper "Xorshift RNGs" adapted for one-byte integers
@@ -0,0 +1,96 @@
+// Keeps emulated time: the CPU runs at a fixed clock rate and the timers tick at exactly
+// 60Hz of that time, however fast or slow the host gets through frames
+pub struct Scheduler {
+    cpu_hz: u32,
+    cycles: u64, // CPU cycles since power on
+    ticks: u64,  // Timer ticks since power on
+}
+
+pub const TIMER_HZ: u64 = 60;
+
+impl Scheduler {
+    pub fn new(cpu_hz: u32) -> Self {
+        Scheduler {
+            cpu_hz,
+            cycles: 0,
+            ticks: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Scheduler::new(self.cpu_hz);
+    }
+
+    pub fn cpu_hz(&self) -> u32 {
+        self.cpu_hz
+    }
+
+    // Whether the CPU has caught up with the next timer tick. Rates that don't divide
+    // evenly by 60 (e.g. 500Hz) spread the extra cycles over the frames.
+    pub fn tick_due(&self) -> bool {
+        self.cycles >= (self.ticks + 1) * self.cpu_hz as u64 / TIMER_HZ
+    }
+
+    pub fn run_cycles(&mut self, cycles: u64) {
+        self.cycles += cycles;
+    }
+
+    // Idle for the rest of the frame
+    pub fn skip_to_tick(&mut self) {
+        self.cycles = self
+            .cycles
+            .max((self.ticks + 1) * self.cpu_hz as u64 / TIMER_HZ);
+    }
+
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cycles run in each of a second's ticks, one cycle at a time
+    fn cycles_per_tick(scheduler: &mut Scheduler) -> Vec<u64> {
+        (0..TIMER_HZ)
+            .map(|_| {
+                let mut cycles = 0;
+                while !scheduler.tick_due() {
+                    scheduler.run_cycles(1);
+                    cycles += 1;
+                }
+                scheduler.tick();
+                cycles
+            })
+            .collect()
+    }
+
+    #[test]
+    fn spreads_cycles_evenly_over_ticks() {
+        for cpu_hz in [500, 600, 1000, 1234] {
+            let mut scheduler = Scheduler::new(cpu_hz);
+            let cycles = cycles_per_tick(&mut scheduler);
+            assert_eq!(cycles.iter().sum::<u64>(), cpu_hz as u64, "{} Hz", cpu_hz);
+
+            let fewest = *cycles.iter().min().unwrap();
+            let most = *cycles.iter().max().unwrap();
+            assert!(most - fewest <= 1, "{} Hz: {:?}", cpu_hz, cycles);
+
+            // And the next second the same way
+            assert_eq!(cycles_per_tick(&mut scheduler), cycles);
+        }
+    }
+
+    #[test]
+    fn skips_the_rest_of_the_tick() {
+        let mut scheduler = Scheduler::new(600);
+        scheduler.run_cycles(3);
+        scheduler.skip_to_tick();
+        assert!(scheduler.tick_due());
+        scheduler.tick();
+
+        // Doesn't take cycles from the next tick
+        assert_eq!(cycles_per_tick(&mut scheduler)[0], 10);
+    }
+}
@@ -0,0 +1,92 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+
+// Signals that end the emulator cleanly, like Ctrl-C does
+const SIGNALS: [libc::c_int; 4] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT];
+
+// Last signal received, or 0
+static SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+// Terminal settings from before the display went into raw mode; None when there's
+// nothing to restore (headless, or the display already cleaned up after itself)
+static SAVED_TERMIOS: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    // Only async-signal-safe work here; the run loop polls for it every frame
+    SIGNAL.store(signal, Ordering::SeqCst);
+}
+
+// Restore the terminal before a panic message is printed, and turn termination signals
+// into a normal exit from the run loop
+pub fn install_handlers() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
+
+    for signal in SIGNALS {
+        unsafe {
+            libc::signal(
+                signal,
+                on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+        }
+    }
+}
+
+pub fn received_signal() -> Option<i32> {
+    match SIGNAL.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal),
+    }
+}
+
+pub fn signal_name(signal: i32) -> &'static str {
+    match signal {
+        libc::SIGINT => "SIGINT",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGQUIT => "SIGQUIT",
+        _ => "signal",
+    }
+}
+
+// Called just before the display switches the terminal to raw mode
+pub fn save_terminal() {
+    let mut termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDOUT_FILENO, &mut termios) } == 0 {
+        *lock_saved() = Some(termios);
+    }
+}
+
+// Called once the display has restored the terminal itself
+pub fn forget_terminal() {
+    *lock_saved() = None;
+}
+
+// Leave raw mode and the alternate screen, if the display is still using them
+pub fn restore_terminal() {
+    if let Some(termios) = lock_saved().take() {
+        unsafe {
+            libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, &termios);
+        }
+
+        let mut stdout = std::io::stdout();
+        let _ = write!(
+            stdout,
+            "{}{}",
+            termion::screen::ToMainScreen,
+            termion::cursor::Show
+        );
+        let _ = stdout.flush();
+    }
+}
+
+// A panic while the lock is held still leaves usable data
+fn lock_saved() -> std::sync::MutexGuard<'static, Option<libc::termios>> {
+    SAVED_TERMIOS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
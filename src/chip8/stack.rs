@@ -1,29 +1,179 @@
-const STACK_SIZE: usize = 12;
+use std::fmt;
+
+// Return addresses the original COSMAC VIP interpreter has room for
+pub const VIP_DEPTH: usize = 12;
+
+// Deepest stack --stack-depth allows; far more than any ROM needs
+pub const MAX_DEPTH: usize = 4096;
+
+// With the VIP memory layout, the stack grows down from here, big-endian
+pub const VIP_STACK_TOP: usize = 0xED0;
 
 #[derive(Debug)]
 pub struct Stack {
-    mem: [u16; STACK_SIZE],
-    ptr: usize,
+    mem: Vec<u16>,
+    in_ram: bool, // Entries live in the VM's memory below VIP_STACK_TOP; mem is unused
+    len: usize,
+    depth: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StackError {
+    Overflow,  // 2NNN with the stack full
+    Underflow, // 00EE with nothing to return to
+}
+
+impl fmt::Display for StackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StackError::Overflow => write!(f, "Stack overflow"),
+            StackError::Underflow => write!(f, "Stack underflow"),
+        }
+    }
 }
 
 impl Stack {
-    pub fn new() -> Self {
+    pub fn new(depth: usize) -> Self {
         Stack {
-            mem: [0; STACK_SIZE],
-            ptr: 0,
+            mem: Vec::new(),
+            in_ram: false,
+            len: 0,
+            depth,
+        }
+    }
+
+    // Stack kept in the VM's memory, where ROMs can see it, as on the VIP
+    pub fn in_ram(depth: usize) -> Self {
+        Stack {
+            mem: Vec::new(),
+            in_ram: true,
+            len: 0,
+            depth: depth.min(VIP_STACK_TOP / 2),
+        }
+    }
+
+    pub fn push(self: &mut Stack, val: u16, ram: &mut [u8]) -> Result<(), StackError> {
+        if self.len >= self.depth {
+            return Err(StackError::Overflow);
+        }
+
+        if self.in_ram {
+            let addr = Stack::ram_addr(self.len);
+            ram[addr..addr + 2].copy_from_slice(&val.to_be_bytes());
+        } else {
+            self.mem.push(val);
+        }
+
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn pop(self: &mut Stack, ram: &[u8]) -> Result<u16, StackError> {
+        if self.len == 0 {
+            return Err(StackError::Underflow);
+        }
+
+        self.len -= 1;
+        if self.in_ram {
+            let addr = Stack::ram_addr(self.len);
+            Ok(u16::from_be_bytes([ram[addr], ram[addr + 1]]))
+        } else {
+            Ok(self.mem.pop().unwrap())
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // Return addresses, outermost call first
+    pub fn entries(&self, ram: &[u8]) -> Vec<u16> {
+        if self.in_ram {
+            (0..self.len)
+                .map(|i| {
+                    let addr = Stack::ram_addr(i);
+                    u16::from_be_bytes([ram[addr], ram[addr + 1]])
+                })
+                .collect()
+        } else {
+            self.mem.clone()
         }
     }
 
-    pub fn push(self: &mut Stack, val: u16) {
-        debug_assert!(self.ptr < STACK_SIZE, "Stack overflow!");
-        self.mem[self.ptr] = val;
-        self.ptr += 1;
+    // Address of each 2NNN still waiting to return, e.g. "0x204 -> 0x31A", or "none"
+    pub fn call_chain(&self, ram: &[u8]) -> String {
+        let calls: Vec<String> = self
+            .entries(ram)
+            .iter()
+            .map(|addr| format!("0x{:03X}", addr.wrapping_sub(2)))
+            .collect();
+
+        if calls.is_empty() {
+            String::from("none")
+        } else {
+            calls.join(" -> ")
+        }
+    }
+
+    fn ram_addr(index: usize) -> usize {
+        VIP_STACK_TOP - 2 * (index + 1)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overflows_past_its_depth() {
+        let mut ram = [0; 0x1000];
+        let mut stack = Stack::new(2);
+        assert_eq!(stack.push(0x202, &mut ram), Ok(()));
+        assert_eq!(stack.push(0x204, &mut ram), Ok(()));
+        assert_eq!(stack.push(0x206, &mut ram), Err(StackError::Overflow));
+        assert_eq!(stack.entries(&ram), [0x202, 0x204]);
+        assert_eq!(stack.call_chain(&ram), "0x200 -> 0x202");
+    }
+
+    #[test]
+    fn underflows_when_empty() {
+        let mut ram = [0; 0x1000];
+        let mut stack = Stack::new(2);
+        assert_eq!(stack.pop(&ram), Err(StackError::Underflow));
+        assert_eq!(stack.call_chain(&ram), "none");
+
+        stack.push(0x202, &mut ram).unwrap();
+        assert_eq!(stack.pop(&ram), Ok(0x202));
+        assert_eq!(stack.pop(&ram), Err(StackError::Underflow));
+    }
+
+    #[test]
+    fn in_ram_grows_down_from_0xecf() {
+        let mut ram = [0; 0x1000];
+        let mut stack = Stack::in_ram(12);
+        stack.push(0x1234, &mut ram).unwrap();
+        stack.push(0x0ABC, &mut ram).unwrap();
+
+        // Big-endian, the first entry in 0xECE-0xECF
+        assert_eq!(ram[0xECC..0xED0], [0x0A, 0xBC, 0x12, 0x34]);
+        assert_eq!(stack.entries(&ram), [0x1234, 0x0ABC]);
+
+        // ROMs can change return addresses in place
+        ram[0xECC..0xECE].copy_from_slice(&[0x03, 0x00]);
+        assert_eq!(stack.pop(&ram), Ok(0x300));
+        assert_eq!(stack.pop(&ram), Ok(0x1234));
+        assert_eq!(stack.pop(&ram), Err(StackError::Underflow));
+    }
+
+    #[test]
+    fn in_ram_depth_fits_below_the_top() {
+        let mut ram = [0; 0x1000];
+        let mut stack = Stack::in_ram(MAX_DEPTH);
+        assert_eq!(stack.depth(), VIP_STACK_TOP / 2);
+        for addr in 0..VIP_STACK_TOP / 2 {
+            stack.push(addr as u16, &mut ram).unwrap();
+        }
 
-    pub fn pop(self: &mut Stack) -> u16 {
-        debug_assert!(self.ptr != 0, "Stack underflow!");
-        let val = self.mem[self.ptr - 1];
-        self.ptr -= 1;
-        val
+        assert_eq!(stack.push(0, &mut ram), Err(StackError::Overflow));
     }
 }
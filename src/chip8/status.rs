@@ -0,0 +1,209 @@
+use std::time::{Duration, Instant};
+
+use super::pacer::PacingStats;
+
+// How often FPS, IPS and IPF are measured
+const WINDOW: Duration = Duration::from_secs(1);
+
+// Counts frames and instructions to show how close the emulator is to its targets
+pub struct Stats {
+    window_start: Instant,
+    frames: u32,
+    instructions: u64,
+    pub fps: f64,
+    pub ips: f64,
+    pub ipf: f64, // Instructions per emulated frame, which turbo doesn't change
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Stats {
+            window_start: Instant::now(),
+            frames: 0,
+            instructions: 0,
+            fps: 0.,
+            ips: 0.,
+            ipf: 0.,
+        }
+    }
+
+    pub fn frame(&mut self, instructions: u32) {
+        self.frames += 1;
+        self.instructions += instructions as u64;
+
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= WINDOW {
+            self.fps = self.frames as f64 / elapsed.as_secs_f64();
+            self.ips = self.instructions as f64 / elapsed.as_secs_f64();
+            self.ipf = self.instructions as f64 / self.frames as f64;
+            *self = Stats {
+                fps: self.fps,
+                ips: self.ips,
+                ipf: self.ipf,
+                ..Stats::new()
+            };
+        }
+    }
+}
+
+// Everything shown in the status bar
+pub struct Status<'a> {
+    pub rom_name: &'a str,
+    pub stats: &'a Stats,
+    pub pacing: &'a PacingStats,
+    pub cpu_hz: Option<u32>, // None with VIP timing
+    pub quirks: String,
+    pub turbo: bool,
+    pub paused: bool,
+    pub notice: Option<&'a str>,
+    pub sound: bool,
+    pub muted: bool,
+    pub recording: bool,
+}
+
+impl Status<'_> {
+    pub fn text(&self) -> String {
+        let (ips_target, ipf_target) = match self.cpu_hz {
+            Some(hz) => (
+                format!(" of {}", format_rate(hz as f64)),
+                format!(" of {}", format_rate(hz as f64 / 60.)),
+            ),
+            None => (String::new(), String::from(" (VIP timing)")),
+        };
+
+        let mut text = format!(
+            "{} | {} FPS ({:.1} ms late) | {} IPS{} | {} IPF{} | {}",
+            self.rom_name,
+            format_rate(self.stats.fps),
+            self.pacing.average_lateness().as_secs_f64() * 1000.,
+            format_rate(self.stats.ips),
+            ips_target,
+            format_rate(self.stats.ipf),
+            ipf_target,
+            self.quirks
+        );
+
+        if self.paused {
+            text += " | PAUSED";
+        } else if self.turbo {
+            text += " | TURBO";
+        }
+
+        if self.recording {
+            text += " | REC";
+        }
+
+        text += match (self.muted, self.sound) {
+            (true, _) => " | muted",
+            (false, true) => " | ♪",
+            (false, false) => "",
+        };
+
+        if let Some(notice) = self.notice {
+            text += " | ";
+            text += notice;
+        }
+
+        text
+    }
+}
+
+// e.g. 600 -> "600", 12345 -> "12.3k"
+fn format_rate(rate: f64) -> String {
+    if rate >= 1_000_000. {
+        format!("{:.1}M", rate / 1_000_000.)
+    } else if rate >= 1_000. {
+        format!("{:.1}k", rate / 1_000.)
+    } else {
+        format!("{:.0}", rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(edit: impl Fn(&mut Status)) -> String {
+        let mut stats = Stats::new();
+        stats.fps = 60.;
+        stats.ips = 598.;
+        stats.ipf = 9.97;
+        let pacing = PacingStats {
+            frames: 4,
+            total_lateness: Duration::from_millis(2),
+            ..Default::default()
+        };
+
+        let mut status = Status {
+            rom_name: "Pong",
+            stats: &stats,
+            pacing: &pacing,
+            cpu_hz: Some(600),
+            quirks: String::from("modern"),
+            turbo: false,
+            paused: false,
+            notice: None,
+            sound: false,
+            muted: false,
+            recording: false,
+        };
+        edit(&mut status);
+        status.text()
+    }
+
+    #[test]
+    fn shows_rates_against_their_targets() {
+        assert_eq!(
+            text(|_| {}),
+            "Pong | 60 FPS (0.5 ms late) | 598 IPS of 600 | 10 IPF of 10 | modern"
+        );
+        assert_eq!(
+            text(|status| status.cpu_hz = None),
+            "Pong | 60 FPS (0.5 ms late) | 598 IPS | 10 IPF (VIP timing) | modern"
+        );
+    }
+
+    #[test]
+    fn shows_markers() {
+        let base = "Pong | 60 FPS (0.5 ms late) | 598 IPS of 600 | 10 IPF of 10 | modern";
+
+        // Paused, turbo, recording, sound, muted, notice
+        let cases = [
+            ((true, false, false, false, false, None), " | PAUSED"),
+            ((false, true, false, false, false, None), " | TURBO"),
+            ((true, true, false, false, false, None), " | PAUSED"),
+            ((false, false, true, false, false, None), " | REC"),
+            ((false, false, false, true, false, None), " | ♪"),
+            ((false, false, false, true, true, None), " | muted"),
+            (
+                (false, true, true, false, false, Some("reset")),
+                " | TURBO | REC | reset",
+            ),
+        ];
+
+        for ((paused, turbo, recording, sound, muted, notice), markers) in cases {
+            let text = text(|status| {
+                status.paused = paused;
+                status.turbo = turbo;
+                status.recording = recording;
+                status.sound = sound;
+                status.muted = muted;
+                status.notice = notice;
+            });
+            assert_eq!(text, format!("{}{}", base, markers));
+        }
+    }
+
+    #[test]
+    fn formats_rates() {
+        for (rate, text) in [
+            (0.4, "0"),
+            (600., "600"),
+            (999.4, "999"),
+            (12_345., "12.3k"),
+            (2_500_000., "2.5M"),
+        ] {
+            assert_eq!(format_rate(rate), text);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// How long each instruction takes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Timing {
+    #[default]
+    Fixed, // Every instruction takes one cycle, at --cpu-hz
+    Vip, // As long as it takes the original COSMAC VIP interpreter
+}
+
+impl std::str::FromStr for Timing {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "fixed" => Ok(Timing::Fixed),
+            "vip" => Ok(Timing::Vip),
+            _ => Err(format!("Unknown timing '{}' (expected fixed or vip)", name)),
+        }
+    }
+}
+
+// Parses back with from_str
+impl std::fmt::Display for Timing {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Timing::Fixed => "fixed",
+            Timing::Vip => "vip",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+// The VIP's 1802 runs at 1.76MHz with 8 clocks per machine cycle, so 3668 machine cycles
+// per 60Hz frame, of which the display interrupt and DMA take about 1070
+pub const VIP_CYCLES_PER_FRAME: u32 = 2598;
+
+// Machine cycles the VIP interpreter spends on an instruction, including fetch and decode.
+// These are approximations of the real routines, close enough that VIP-era games run at
+// their original pace; vx is the value of register X before the instruction ran.
+pub fn vip_cycles(opcode: u16, vx: u8, skipped: bool) -> u64 {
+    let x = ((opcode & 0x0F00) >> 8) as u64;
+    let n = (opcode & 0x000F) as u64;
+    let skip = if skipped { 4 } else { 0 };
+
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => 24,
+            _ => 10, // 00EE, and machine code routines as if they returned at once
+        },
+        0x1000 => 12,
+        0x2000 => 26,
+        0x3000 | 0x4000 | 0x5000 | 0x9000 | 0xE000 => 10 + skip,
+        0x6000 => 6,
+        0x7000 => 10,
+        0x8000 => 44,
+        0xA000 => 12,
+        0xB000 => 22,
+        0xC000 => 36,
+        0xD000 => {
+            // Sprites not on a byte boundary are shifted into two bytes, a bit at a time
+            let shift = (vx % 8) as u64;
+            let row = if shift == 0 { 8 } else { 16 + 2 * shift };
+            26 + n * row
+        }
+        _ => match opcode & 0x00FF {
+            0x1E | 0x29 => 16,
+            0x0A => 20,
+            0x33 => {
+                // Counts each digit out by repeated subtraction
+                let digits = (vx / 100 + vx / 10 % 10 + vx % 10) as u64;
+                84 + 16 * digits
+            }
+            0x55 | 0x65 => 14 + 14 * (x + 1),
+            _ => 10, // FX07, FX15, FX18
+        },
+    }
+}
@@ -0,0 +1,190 @@
+use super::cdp1802::{Bus, Cpu};
+
+// A whole COSMAC VIP: 1802 CPU, 4KB of RAM, the 512-byte monitor ROM at 0x8000, the 1861
+// video chip and the hex keypad. The CHIP-8 interpreter is ordinary 1802 code in RAM.
+const RAM_SIZE: usize = 4096;
+pub const FIRMWARE_SIZE: usize = 512; // Both the monitor ROM and the CHIP-8 interpreter
+
+// The 1861 draws 262 lines of 14 machine cycles each, 60 times a second
+const CYCLES_PER_LINE: u64 = 14;
+const CYCLES_PER_FRAME: u64 = 262 * CYCLES_PER_LINE;
+
+// 128 lines of 8 bytes are fetched by DMA from line 80 on. The interrupt comes 29 cycles
+// before the first DMA, which the interpreter's display routine is timed around, and EF1
+// is set for the 4 lines before the display area and its last 4 lines.
+const FIRST_DMA_CYCLE: u64 = 80 * CYCLES_PER_LINE;
+const INTERRUPT_CYCLE: u64 = FIRST_DMA_CYCLE - 29;
+const DISPLAY_LINES: usize = 128;
+const BYTES_PER_LINE: usize = 8;
+const EF1_CYCLES: [(u64, u64); 2] = [
+    (76 * CYCLES_PER_LINE, 80 * CYCLES_PER_LINE),
+    (204 * CYCLES_PER_LINE, 208 * CYCLES_PER_LINE),
+];
+
+// Files the user supplies from their own VIP
+pub struct Firmware {
+    pub monitor: Vec<u8>,     // Mapped at 0x8000
+    pub interpreter: Vec<u8>, // Loaded at 0x0000, like the VIP's CHIP-8 cassette
+}
+
+struct Hardware {
+    ram: [u8; RAM_SIZE],
+    monitor: [u8; FIRMWARE_SIZE],
+    monitor_at_zero: bool, // After a reset, until the first address with A15 set
+    display_on: bool,
+    key_latch: u8,
+    keys: u16, // Bit N = key N held
+    frame_cycle: u64,
+}
+
+impl Bus for Hardware {
+    fn read(&mut self, addr: u16) -> u8 {
+        if addr & 0x8000 != 0 {
+            self.monitor_at_zero = false;
+            return self.monitor[addr as usize % FIRMWARE_SIZE];
+        }
+
+        if self.monitor_at_zero {
+            self.monitor[addr as usize % FIRMWARE_SIZE]
+        } else {
+            self.ram[addr as usize % RAM_SIZE]
+        }
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        if addr & 0x8000 == 0 {
+            self.ram[addr as usize % RAM_SIZE] = val;
+        }
+    }
+
+    fn output(&mut self, port: u8, val: u8) {
+        match port {
+            1 => self.display_on = false,
+            2 => self.key_latch = val & 0x0F,
+            _ => {}
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.display_on = true;
+        }
+
+        0
+    }
+
+    fn flag(&self, ef: u8) -> bool {
+        match ef {
+            1 => EF1_CYCLES
+                .iter()
+                .any(|&(start, end)| (start..end).contains(&self.frame_cycle)),
+            3 => self.keys & (1 << self.key_latch) != 0,
+            _ => false, // EF2 is the cassette input and EF4 the IN button
+        }
+    }
+}
+
+pub struct Vip {
+    cpu: Cpu,
+    hardware: Hardware,
+    lines: [[u8; BYTES_PER_LINE]; DISPLAY_LINES], // Fetched by DMA in the last frame
+    overrun: u64, // Cycles the last frame's final instruction ran into this one
+}
+
+impl Vip {
+    pub fn new(firmware: &Firmware, rom: &[u8], rom_start: usize) -> Self {
+        let mut hardware = Hardware {
+            ram: [0; RAM_SIZE],
+            monitor: [0; FIRMWARE_SIZE],
+            monitor_at_zero: true,
+            display_on: false,
+            key_latch: 0,
+            keys: 0,
+            frame_cycle: 0,
+        };
+
+        hardware.monitor[..firmware.monitor.len()].copy_from_slice(&firmware.monitor);
+        hardware.ram[..firmware.interpreter.len()].copy_from_slice(&firmware.interpreter);
+        hardware.ram[rom_start..rom_start + rom.len()].copy_from_slice(rom);
+
+        Vip {
+            cpu: Cpu::new(),
+            hardware,
+            lines: [[0; BYTES_PER_LINE]; DISPLAY_LINES],
+            overrun: 0,
+        }
+    }
+
+    // Run one frame of the 1861 with these keys held; returns the instructions run
+    pub fn run_frame(&mut self, keys: u16) -> u32 {
+        self.hardware.keys = keys;
+        self.lines = [[0; BYTES_PER_LINE]; DISPLAY_LINES];
+
+        let mut cycle = self.overrun;
+        let mut interrupted = false;
+        let mut dma_line = 0;
+        let mut instructions = 0;
+        while cycle < CYCLES_PER_FRAME {
+            self.hardware.frame_cycle = cycle;
+
+            if !interrupted && cycle >= INTERRUPT_CYCLE {
+                interrupted = true;
+                if self.hardware.display_on {
+                    cycle += self.cpu.interrupt();
+                }
+
+                continue;
+            }
+
+            if dma_line < DISPLAY_LINES
+                && cycle >= FIRST_DMA_CYCLE + dma_line as u64 * CYCLES_PER_LINE
+            {
+                if self.hardware.display_on {
+                    for byte in self.lines[dma_line].iter_mut() {
+                        *byte = self.cpu.dma_out(&mut self.hardware);
+                    }
+
+                    cycle += BYTES_PER_LINE as u64;
+                }
+
+                dma_line += 1;
+                continue;
+            }
+
+            if !self.cpu.idle {
+                instructions += 1;
+            }
+
+            cycle += self.cpu.step(&mut self.hardware);
+        }
+
+        self.overrun = cycle - CYCLES_PER_FRAME;
+        instructions
+    }
+
+    // Q drives the speaker
+    pub fn tone(&self) -> bool {
+        self.cpu.q
+    }
+
+    // The CHIP-8 interpreter shows each row of its 64x32 screen on 4 lines (or of HIRES's
+    // 64x64 screen on 2)
+    pub fn pixel(&self, row: usize, col: usize, height: usize) -> bool {
+        let line = row * DISPLAY_LINES / height;
+        self.lines[line][col / 8] & (0x80 >> (col % 8)) != 0
+    }
+
+    // CPU registers, for error reports
+    pub fn state_summary(&self) -> String {
+        format!(
+            "1802 P: {:X}, X: {:X}, D: 0x{:02X}, DF: {}, Q: {}, IE: {}\nRegisters: {:04X?}",
+            self.cpu.p,
+            self.cpu.x,
+            self.cpu.d,
+            self.cpu.df as u8,
+            self.cpu.q as u8,
+            self.cpu.ie as u8,
+            self.cpu.r
+        )
+    }
+}
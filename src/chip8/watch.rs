@@ -0,0 +1,47 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const POLL_INTERVAL: u32 = 15; // Frames between checks (4 times a second)
+
+// Polls a file for changes, e.g. a ROM being rebuilt by an external assembler
+pub struct FileWatcher {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>, // Modification time and size
+    changed: bool,                    // Changed since the last poll, and may still be changing
+    frames_until_poll: u32,
+}
+
+impl FileWatcher {
+    pub fn new(path: &Path) -> Self {
+        FileWatcher {
+            path: path.to_path_buf(),
+            stamp: stamp(path),
+            changed: false,
+            frames_until_poll: POLL_INTERVAL,
+        }
+    }
+
+    // Call once per frame. True once the file has changed and then stayed the same for
+    // a whole poll interval, so half-written files aren't picked up.
+    pub fn poll(&mut self) -> bool {
+        if self.frames_until_poll > 0 {
+            self.frames_until_poll -= 1;
+            return false;
+        }
+
+        self.frames_until_poll = POLL_INTERVAL;
+        let stamp = stamp(&self.path);
+        if stamp != self.stamp {
+            self.stamp = stamp;
+            self.changed = true;
+            return false;
+        }
+
+        std::mem::take(&mut self.changed) && stamp.is_some()
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
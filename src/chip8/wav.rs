@@ -0,0 +1,61 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const BITS_PER_SAMPLE: u16 = 16;
+const NUM_CHANNELS: u16 = 1;
+
+// Write 16-bit mono PCM samples as a RIFF/WAVE file
+pub fn write(path: &Path, sample_rate: u32, samples: &[i16]) -> Result<(), std::io::Error> {
+    let mut output = BufWriter::new(File::create(path)?);
+    let block_align = NUM_CHANNELS * BITS_PER_SAMPLE / 8;
+    let data_len = (samples.len() * block_align as usize) as u32;
+
+    output.write_all(b"RIFF")?;
+    output.write_all(&(36 + data_len).to_le_bytes())?;
+    output.write_all(b"WAVE")?;
+
+    // Format chunk: PCM
+    output.write_all(b"fmt ")?;
+    output.write_all(&16u32.to_le_bytes())?;
+    output.write_all(&1u16.to_le_bytes())?;
+    output.write_all(&NUM_CHANNELS.to_le_bytes())?;
+    output.write_all(&sample_rate.to_le_bytes())?;
+    output.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+    output.write_all(&block_align.to_le_bytes())?;
+    output.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+    output.write_all(b"data")?;
+    output.write_all(&data_len.to_le_bytes())?;
+    for sample in samples {
+        output.write_all(&sample.to_le_bytes())?;
+    }
+
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_pcm_header_and_samples() {
+        let path = std::env::temp_dir().join(format!("chip8-wav-test-{}.wav", std::process::id()));
+        write(&path, 8000, &[0, 1, -1, i16::MAX]).unwrap();
+        let wav = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b"RIFF");
+        expected.extend_from_slice(&44u32.to_le_bytes()); // 36 + 8 bytes of samples
+        expected.extend_from_slice(b"WAVEfmt ");
+        expected.extend_from_slice(&[16, 0, 0, 0, 1, 0, 1, 0]); // PCM, mono
+        expected.extend_from_slice(&8000u32.to_le_bytes()); // Sample rate
+        expected.extend_from_slice(&16000u32.to_le_bytes()); // Byte rate
+        expected.extend_from_slice(&[2, 0, 16, 0]); // Block align, bits per sample
+        expected.extend_from_slice(b"data");
+        expected.extend_from_slice(&8u32.to_le_bytes());
+        expected.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0xFF, 0xFF, 0xFF, 0x7F]);
+        assert_eq!(wav, expected);
+    }
+}
@@ -0,0 +1,716 @@
+use std::path::PathBuf;
+
+use crate::chip8;
+use crate::config::{self, Config};
+use crate::romdb::Database;
+
+pub const USAGE: &str = "\
+USAGE:
+    chip8 [run] <ROM> [OPTIONS]      Play a ROM
+    chip8 test <ROM> [OPTIONS]       Run a ROM headless, then print its final screen
+    chip8 disasm <ROM>               Print a ROM's instructions
+    chip8 asm <SOURCE> [-o <ROM>]    Assemble a ROM (same syntax as disasm's output)
+    chip8 info <ROM>                 Print details about a ROM
+    chip8 --help | --version";
+
+pub enum Command {
+    Run(RunCommand),
+    Test(RunCommand),
+    Disasm { rom: PathBuf },
+    Asm { source: PathBuf, output: PathBuf },
+    Info { rom: PathBuf },
+    Help,
+    Version,
+}
+
+// Parsed run or test command; options are only applied once the ROM is known,
+// since the ROM database and config file can have per-ROM settings
+pub struct RunCommand {
+    pub rom: PathBuf,
+    is_test: bool,
+    config_path: Option<PathBuf>,
+    use_config: bool,
+    database_path: Option<PathBuf>,
+    use_database: bool,
+    settings: Vec<(String, String)>, // Command-line options, in order
+}
+
+// Everything needed to start the VM, for both run and test
+pub struct RunArgs {
+    pub options: chip8::Options,
+    pub play_input: Option<PathBuf>,
+    pub play_until: Option<usize>,        // Frame to stop the movie at
+    pub vip_rom: Option<PathBuf>,         // COSMAC VIP monitor ROM
+    pub vip_interpreter: Option<PathBuf>, // CHIP-8 interpreter to run on it
+    pub expect: Option<String>,           // Expected screen hash (test only)
+}
+
+struct OptionSpec {
+    name: &'static str,
+    value: Option<&'static str>, // Placeholder for the option's value, if it takes one
+    help: &'static str,
+}
+
+const fn spec(name: &'static str, value: Option<&'static str>, help: &'static str) -> OptionSpec {
+    OptionSpec { name, value, help }
+}
+
+// Options accepted by run and test
+#[rustfmt::skip]
+const RUN_OPTIONS: [OptionSpec; 36] = [
+    spec("platform", Some("PLATFORM"), "chip8 (default), hires for 64x64 CHIP-8, or chip8x"),
+    spec("quirks", Some("PROFILE|QUIRKS"), "legacy, vip, modern (default), or e.g. shift,load-store"),
+    spec("legacy", None, "Same as --quirks legacy"),
+    spec("speed", Some("IPF"), "Instructions per frame (default 10)"),
+    spec("cpu-hz", Some("HZ"), "Instructions per second (default 600; same as --speed x 60)"),
+    spec("timing", Some("TIMING"), "fixed (default), or vip for each instruction's VIP cycle cost"),
+    spec("stack-depth", Some("CALLS"), "Nested calls before a stack overflow (default 12 on the VIP)"),
+    spec("memory", Some("LAYOUT"), "separate (default), or vip for the stack and screen in RAM"),
+    spec("machine-code", Some("POLICY"), "0NNN: emulate known routines (default), ignore, or error"),
+    spec("vip-rom", Some("FILE"), "COSMAC VIP monitor ROM, for --vip-interpreter"),
+    spec("vip-interpreter", Some("FILE"), "Run this CHIP-8 interpreter on an emulated COSMAC VIP"),
+    spec("seed", Some("0-255"), "Fixed RNG seed"),
+    spec("keymap", Some("LAYOUT"), "qwerty (default), azerty, dvorak, colemak, or 16 keys"),
+    spec("arrows", Some("U,D,L,R"), "Keys (0-F) to press with the arrow keys"),
+    spec("renderer", Some("RENDERER"), "text (default), sixel or kitty"),
+    spec("status", None, "Show FPS, speed and other details below the screen"),
+    spec("palette", Some("RRGGBB,RRGGBB"), "Off and on colors of recorded frames and images"),
+    spec("scale", Some("FACTOR"), "Size of each pixel in recorded frames and images (default 4)"),
+    spec("record", Some("GIF"), "Record to a GIF without a terminal (headless)"),
+    spec("frames", Some("COUNT"), "Frames to run headless (default 600, or whole movie)"),
+    spec("record-input", Some("MOVIE"), "Save each frame's key presses"),
+    spec("play-input", Some("MOVIE"), "Replay key presses, then quit"),
+    spec("play-until", Some("FRAME"), "Stop --play-input after this many frames"),
+    spec("handoff", None, "Hand control back after --play-input instead of quitting"),
+    spec("watch", None, "Reload the ROM (and replay --play-input) when the file changes"),
+    spec("audio-out", Some("WAV"), "Render the buzzer to a WAV file on exit"),
+    spec("tone", Some("HZ"), "Buzzer frequency (default 442)"),
+    spec("waveform", Some("WAVE"), "square (default), sine, triangle or noise"),
+    spec("volume", Some("0-100"), "Buzzer volume (default 25)"),
+    spec("mute", None, "Start with the buzzer muted"),
+    spec("debug", None, "Step through instructions (debug builds only)"),
+    spec("expect", Some("SHA1"), "test: fail unless the final screen has this hash"),
+    spec("config", Some("PATH"), "Config file to use instead of the default one"),
+    spec("no-config", None, "Ignore the config file"),
+    spec("database", Some("DIR"), "ROM database to use instead of the default one"),
+    spec("no-database", None, "Don't look up settings in the ROM database"),
+];
+
+pub fn help() -> String {
+    let mut text = format!(
+        "chip8 {}\nA lightweight CHIP-8 emulator for the terminal\n\n{}\n\nRUN/TEST OPTIONS:\n",
+        env!("CARGO_PKG_VERSION"),
+        USAGE
+    );
+
+    for option in RUN_OPTIONS.iter() {
+        let flag = match option.value {
+            Some(value) => format!("--{} <{}>", option.name, value),
+            None => format!("--{}", option.name),
+        };
+
+        text += &format!("    {:<32}{}\n", flag, option.help);
+    }
+
+    text += "\nHOTKEYS:\n";
+    for (key, action) in chip8::HOTKEY_HELP.iter() {
+        text += &format!("    {:<8}{}\n", key, action);
+    }
+
+    text
+}
+
+pub fn parse(args: &[String]) -> Result<Command, String> {
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        return Ok(Command::Help);
+    }
+
+    if args.iter().any(|arg| arg == "--version" || arg == "-V") {
+        return Ok(Command::Version);
+    }
+
+    let (subcommand, rest) = match args.first().map(String::as_str) {
+        Some(name @ ("run" | "test" | "disasm" | "asm" | "info")) => (name, &args[1..]),
+        Some(_) => ("run", args), // "chip8 <ROM>" is short for "chip8 run <ROM>"
+        None => return Err(String::from("No ROM given")),
+    };
+
+    match subcommand {
+        "run" | "test" => {
+            let run_command = parse_run_command(rest, subcommand == "test")?;
+            if subcommand == "test" {
+                Ok(Command::Test(run_command))
+            } else {
+                Ok(Command::Run(run_command))
+            }
+        }
+        "asm" => {
+            let (source, output) = match rest {
+                [source] => (source, None),
+                [source, flag, output] if flag == "-o" || flag == "--output" => {
+                    (source, Some(output))
+                }
+                _ => return Err(String::from("asm expects <SOURCE> [-o <ROM>]")),
+            };
+
+            let source = PathBuf::from(source);
+            let output = match output {
+                Some(output) => PathBuf::from(output),
+                None => source.with_extension("ch8"),
+            };
+
+            Ok(Command::Asm { source, output })
+        }
+        _ => {
+            let rom = match rest {
+                [rom] if !rom.starts_with("--") => PathBuf::from(rom),
+                _ => return Err(format!("{} expects just a ROM path", subcommand)),
+            };
+
+            if subcommand == "disasm" {
+                Ok(Command::Disasm { rom })
+            } else {
+                Ok(Command::Info { rom })
+            }
+        }
+    }
+}
+
+fn parse_run_command(args: &[String], is_test: bool) -> Result<RunCommand, String> {
+    let mut rom = None;
+    let mut run_command = RunCommand {
+        rom: PathBuf::new(),
+        is_test,
+        config_path: None,
+        use_config: true,
+        database_path: None,
+        use_database: true,
+        settings: Vec::new(),
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let name = match arg.strip_prefix("--") {
+            Some(name) => name,
+            None if rom.is_none() => {
+                rom = Some(PathBuf::from(arg));
+                continue;
+            }
+            None => return Err(format!("Unexpected argument '{}'", arg)),
+        };
+
+        // Accept both "--name value" and "--name=value"
+        let (name, inline_value) = match name.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (name, None),
+        };
+
+        let option = RUN_OPTIONS
+            .iter()
+            .find(|option| option.name == name)
+            .ok_or_else(|| format!("Unknown option '--{}'", name))?;
+
+        let value = match (option.value, inline_value) {
+            (Some(_), Some(value)) => value,
+            (Some(_), None) => args
+                .next()
+                .cloned()
+                .ok_or_else(|| format!("--{} expects a value", name))?,
+            (None, Some(_)) => return Err(format!("--{} doesn't take a value", name)),
+            (None, None) => String::new(),
+        };
+
+        match name {
+            "config" => run_command.config_path = Some(PathBuf::from(value)),
+            "no-config" => run_command.use_config = false,
+            "database" => run_command.database_path = Some(PathBuf::from(value)),
+            "no-database" => run_command.use_database = false,
+            "expect" if !is_test => {
+                return Err(String::from("--expect only works with the test subcommand"))
+            }
+            _ => run_command.settings.push((name.to_string(), value)),
+        }
+    }
+
+    run_command.rom = rom.ok_or_else(|| String::from("No ROM given"))?;
+    Ok(run_command)
+}
+
+impl RunCommand {
+    // Apply the ROM database's settings for this ROM, then the config file's,
+    // then the command-line options
+    pub fn resolve(self, rom: &[u8]) -> Result<RunArgs, String> {
+        let mut run_args = RunArgs {
+            options: chip8::Options {
+                headless: self.is_test,
+                ..Default::default()
+            },
+            play_input: None,
+            play_until: None,
+            vip_rom: None,
+            vip_interpreter: None,
+            expect: None,
+        };
+
+        let rom_hash = chip8::rom_hash(rom);
+        let rom_name = self
+            .rom
+            .file_name()
+            .map_or(String::new(), |name| name.to_string_lossy().to_string());
+        run_args.options.rom_name = rom_name.clone();
+        run_args.options.rom_path = Some(self.rom.clone());
+
+        if self.use_database {
+            let database = match &self.database_path {
+                Some(path) => Database::load(path)?,
+                None => Database::load_default()?,
+            };
+
+            if let Some(rom_info) = database.lookup(&rom_hash) {
+                run_args.options.rom_name = rom_info.title;
+                for (name, value) in rom_info.settings {
+                    apply_option(&mut run_args, &name, &value)
+                        .map_err(|err| format!("ROM database: {}", err))?;
+                }
+            }
+        }
+
+        if self.use_config {
+            let config = match &self.config_path {
+                Some(path) => Config::load(path)?,
+                None => Config::load_default()?,
+            };
+
+            let config_name = match &self.config_path {
+                Some(path) => path.display().to_string(),
+                None => {
+                    config::default_path().map_or(String::new(), |path| path.display().to_string())
+                }
+            };
+
+            for (name, value) in config.settings_for(&rom_name, &rom_hash) {
+                apply_config_setting(&mut run_args, &name, &value)
+                    .map_err(|err| format!("{}: {}", config_name, err))?;
+            }
+        }
+
+        for (name, value) in &self.settings {
+            apply_option(&mut run_args, name, value)?;
+        }
+
+        // Input movies replay key presses frame-by-frame, with the settings they were
+        // recorded with
+        if let Some(path) = run_args.play_input.clone() {
+            let mut movie = chip8::Movie::load(&path)?;
+            if movie.rom_hash != rom_hash {
+                return Err(String::from(
+                    "Input movie was recorded with a different ROM",
+                ));
+            }
+
+            for (name, value) in &movie.settings {
+                apply_option(&mut run_args, name, value)
+                    .map_err(|err| format!("Input movie: {}", err))?;
+            }
+
+            if let Some(frames) = run_args.play_until {
+                movie.truncate(frames);
+            }
+
+            run_args.options.playback = Some(movie);
+        }
+
+        if run_args.options.debug_mode && run_args.options.headless {
+            return Err(String::from(
+                "Debug mode needs a terminal; it can't run headless",
+            ));
+        }
+
+        let platform = run_args.options.platform;
+        if rom.len() > platform.max_rom_size() {
+            return Err(format!(
+                "ROM is {} bytes; at most {} fit in memory on this platform",
+                rom.len(),
+                platform.max_rom_size()
+            ));
+        }
+
+        // The VIP layout's stack sits where HIRES's second page of screen would be
+        let vip_memory = run_args.options.memory_layout == chip8::MemoryLayout::Vip;
+        if platform == chip8::Platform::Hires && vip_memory {
+            return Err(String::from(
+                "--memory vip only fits the 64x32 screen, not --platform hires",
+            ));
+        }
+
+        if vip_memory && rom.len() > run_args.options.max_rom_size() {
+            return Err(format!(
+                "ROM is {} bytes; at most {} fit below the stack and screen with --memory vip",
+                rom.len(),
+                run_args.options.max_rom_size()
+            ));
+        }
+
+        match (&run_args.vip_rom, &run_args.vip_interpreter) {
+            // The interpreter runs plain CHIP-8; other platforms need their own
+            (_, Some(_)) if platform != chip8::Platform::Chip8 => {
+                return Err(format!(
+                    "--vip-interpreter runs plain CHIP-8, so it can't be used with --platform {}",
+                    platform
+                ))
+            }
+            (Some(monitor), Some(interpreter)) => {
+                run_args.options.vip_firmware = Some(chip8::VipFirmware {
+                    monitor: read_firmware(monitor, "VIP monitor ROM")?,
+                    interpreter: read_firmware(interpreter, "CHIP-8 interpreter")?,
+                });
+            }
+            (None, Some(_)) => {
+                return Err(String::from(
+                    "--vip-interpreter needs the VIP's monitor ROM too (--vip-rom)",
+                ))
+            }
+            _ => {}
+        }
+
+        Ok(run_args)
+    }
+}
+
+// The VIP's monitor ROM and CHIP-8 interpreter are each 512 bytes
+fn read_firmware(path: &std::path::Path, name: &str) -> Result<Vec<u8>, String> {
+    let data = std::fs::read(path)
+        .map_err(|err| format!("Cannot open {} '{}': {}", name, path.display(), err))?;
+    if data.len() > chip8::VIP_FIRMWARE_SIZE {
+        return Err(format!(
+            "{} is {} bytes; expected at most {}",
+            name,
+            data.len(),
+            chip8::VIP_FIRMWARE_SIZE
+        ));
+    }
+
+    Ok(data)
+}
+
+// Config files spell flags out as "mute = true" or "mute = false"
+fn apply_config_setting(run_args: &mut RunArgs, name: &str, value: &str) -> Result<(), String> {
+    let is_flag = RUN_OPTIONS
+        .iter()
+        .any(|option| option.name == name && option.value.is_none());
+    if !is_flag {
+        return apply_option(run_args, name, value);
+    }
+
+    match value {
+        "true" | "yes" | "on" | "1" => apply_option(run_args, name, ""),
+        "false" | "no" | "off" | "0" => Ok(()),
+        _ => Err(format!("'{}' should be true or false", name)),
+    }
+}
+
+// Apply a single option (without its leading "--") to run arguments
+pub fn apply_option(run_args: &mut RunArgs, name: &str, value: &str) -> Result<(), String> {
+    let options = &mut run_args.options;
+    match name {
+        "platform" => options.platform = value.parse()?,
+        "quirks" => options.quirks = chip8::Quirks::parse(value)?,
+        "legacy" => options.quirks = chip8::Quirks::LEGACY,
+        "speed" => {
+            let instructions_per_frame: u32 = parse_positive(name, value)?;
+            options.cpu_hz = instructions_per_frame.saturating_mul(60);
+        }
+        "cpu-hz" => options.cpu_hz = parse_positive(name, value)?,
+        "stack-depth" => {
+            let depth: usize = parse_positive(name, value)?;
+            if depth > chip8::MAX_STACK_DEPTH {
+                return Err(format!(
+                    "--stack-depth must be at most {}",
+                    chip8::MAX_STACK_DEPTH
+                ));
+            }
+
+            options.stack_depth = Some(depth);
+        }
+        "memory" => options.memory_layout = value.parse()?,
+        "machine-code" => options.machine_code = value.parse()?,
+        "vip-rom" => run_args.vip_rom = Some(PathBuf::from(value)),
+        "vip-interpreter" => run_args.vip_interpreter = Some(PathBuf::from(value)),
+        "seed" => options.seed = Some(parse_number(name, value)?),
+        "keymap" => options.keymap = chip8::Keymap::parse(value)?,
+        "arrows" => options.arrows = Some(parse_arrows(value)?),
+        "timing" => options.timing = value.parse()?,
+        "renderer" => options.renderer = value.parse()?,
+        "status" => options.status_bar = true,
+        "palette" => options.palette = chip8::Palette::parse(value)?,
+        "scale" => options.scale = parse_positive(name, value)?,
+        "record" => {
+            options.record_path = Some(PathBuf::from(value));
+            options.headless = true;
+        }
+        "frames" => options.headless_frames = Some(parse_positive(name, value)?),
+        "record-input" => options.record_input = Some(PathBuf::from(value)),
+        "play-input" => run_args.play_input = Some(PathBuf::from(value)),
+        "play-until" => run_args.play_until = Some(parse_number(name, value)?),
+        "handoff" => options.handoff = true,
+        "watch" => options.watch = true,
+        "audio-out" => options.audio_out = Some(PathBuf::from(value)),
+        "tone" => {
+            let frequency: f32 = parse_number(name, value)?;
+            if !(frequency > 0. && frequency < 20_000.) {
+                return Err(String::from("--tone must be between 0 and 20000 Hz"));
+            }
+
+            options.tone.frequency = frequency;
+        }
+        "waveform" => options.tone.waveform = value.parse()?,
+        "volume" => {
+            let percent: u8 = parse_number(name, value)?;
+            if percent > 100 {
+                return Err(String::from("--volume must be between 0 and 100"));
+            }
+
+            options.tone.volume = percent as f32 / 100.;
+        }
+        "mute" => options.muted = true,
+        "debug" => options.debug_mode = true,
+        "expect" => run_args.expect = Some(value.to_lowercase()),
+        _ => return Err(format!("Unknown option '--{}'", name)),
+    }
+
+    Ok(())
+}
+
+// "U,D,L,R": the keypad key (0-F) pressed by each arrow key
+fn parse_arrows(value: &str) -> Result<[u8; 4], String> {
+    let keys: Vec<u8> = value
+        .split(',')
+        .map(|key| {
+            u8::from_str_radix(key.trim(), 16)
+                .ok()
+                .filter(|&key| key < 16)
+        })
+        .collect::<Option<_>>()
+        .ok_or_else(|| format!("Invalid value '{}' for --arrows", value))?;
+
+    keys.try_into()
+        .map_err(|_| String::from("--arrows expects 4 keys: up, down, left, right"))
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value '{}' for --{}", value, name))
+}
+
+fn parse_positive<T: std::str::FromStr + Default + PartialOrd>(
+    name: &str,
+    value: &str,
+) -> Result<T, String> {
+    let number: T = parse_number(name, value)?;
+    if number <= T::default() {
+        return Err(format!("--{} must be positive", name));
+    }
+
+    Ok(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: [u8; 2] = [0x12, 0x00];
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("chip8-{}-{}", name, std::process::id()))
+    }
+
+    fn resolve(args: &[&str], rom: &[u8]) -> Result<RunArgs, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        match parse(&args)? {
+            Command::Run(run_command) | Command::Test(run_command) => run_command.resolve(rom),
+            _ => panic!("{:?} isn't a run command", args),
+        }
+    }
+
+    // Without the user's own config file or ROM database
+    fn resolve_error(args: &[&str], rom: &[u8]) -> String {
+        let args: Vec<&str> = args
+            .iter()
+            .chain(&["--no-config", "--no-database"])
+            .copied()
+            .collect();
+        match resolve(&args, rom) {
+            Err(err) => err,
+            Ok(_) => panic!("{:?} resolved", args),
+        }
+    }
+
+    #[test]
+    fn later_sources_override_earlier_ones() {
+        let hash = chip8::rom_hash(&ROM);
+        let database = temp_path("cli-database");
+        std::fs::create_dir_all(&database).unwrap();
+        for (file, text) in [
+            ("sha1-hashes.json", format!(r#"{{"{}": 0}}"#, hash)),
+            (
+                "programs.json",
+                format!(
+                    r#"[{{"title": "Test", "roms": {{"{}": {{"platforms": ["originalChip8"]}}}}}}]"#,
+                    hash
+                ),
+            ),
+            (
+                "platforms.json",
+                String::from(r#"[{"id": "originalChip8", "defaultTickrate": 15}]"#),
+            ),
+        ] {
+            std::fs::write(database.join(file), text).unwrap();
+        }
+
+        let config = temp_path("cli-config.ini");
+        std::fs::write(&config, "speed = 20\nstack-depth = 14\nvolume = 40\n").unwrap();
+
+        let movie = temp_path("cli-movie");
+        std::fs::write(
+            &movie,
+            format!(
+                "chip8-movie 2\nrom-sha1 {}\nseed 1\nquirks none\nplatform chip8\ncpu-hz 1800\n\
+                 timing fixed\nstack-depth 30\nmemory separate\nmachine-code emulate\n",
+                hash
+            ),
+        )
+        .unwrap();
+
+        let (database, config, movie) = (
+            database.to_str().unwrap(),
+            config.to_str().unwrap(),
+            movie.to_str().unwrap(),
+        );
+        let database_only = ["test", "rom.ch8", "--database", database, "--no-config"];
+        let with_config = [
+            "test",
+            "rom.ch8",
+            "--database",
+            database,
+            "--config",
+            config,
+        ];
+        let with_options = [&with_config[..], &["--cpu-hz", "1500", "--seed", "7"]].concat();
+        let with_movie = [&with_options[..], &["--play-input", movie]].concat();
+
+        let options = resolve(&database_only, &ROM).unwrap().options;
+        assert_eq!(options.rom_name, "Test");
+        assert_eq!(options.quirks.profile(), "legacy"); // originalChip8 quirks
+        assert_eq!(options.cpu_hz, 15 * 60);
+
+        let options = resolve(&with_config, &ROM).unwrap().options;
+        assert_eq!(options.cpu_hz, 20 * 60);
+        assert_eq!(options.stack_depth, Some(14));
+        assert_eq!(options.tone.volume, 0.4);
+
+        let options = resolve(&with_options, &ROM).unwrap().options;
+        assert_eq!(options.cpu_hz, 1500);
+        assert_eq!(options.stack_depth, Some(14));
+        assert_eq!(options.seed, Some(7));
+
+        let options = resolve(&with_movie, &ROM).unwrap().options;
+        assert_eq!(options.cpu_hz, 1800);
+        assert_eq!(options.stack_depth, Some(30));
+        assert_eq!(options.tone.volume, 0.4);
+        assert!(options.playback.is_some());
+
+        std::fs::remove_dir_all(database).unwrap();
+        std::fs::remove_file(config).unwrap();
+        std::fs::remove_file(movie).unwrap();
+    }
+
+    #[test]
+    fn rejects_conflicting_options() {
+        for (args, error) in [
+            (
+                &["test", "rom.ch8", "--debug"][..],
+                "Debug mode needs a terminal",
+            ),
+            (
+                &["rom.ch8", "--record", "out.gif", "--debug"],
+                "Debug mode needs a terminal",
+            ),
+            (
+                &["rom.ch8", "--platform", "hires", "--memory", "vip"],
+                "--memory vip only fits the 64x32 screen",
+            ),
+            (
+                &[
+                    "rom.ch8",
+                    "--platform",
+                    "chip8x",
+                    "--vip-rom",
+                    "a",
+                    "--vip-interpreter",
+                    "b",
+                ],
+                "--vip-interpreter runs plain CHIP-8",
+            ),
+            (
+                &["rom.ch8", "--vip-interpreter", "b"],
+                "--vip-interpreter needs the VIP's monitor ROM",
+            ),
+            (
+                &["rom.ch8", "--stack-depth", "0"],
+                "--stack-depth must be positive",
+            ),
+            (
+                &["rom.ch8", "--stack-depth", "5000"],
+                "--stack-depth must be at most 4096",
+            ),
+        ] {
+            let err = resolve_error(args, &ROM);
+            assert!(err.starts_with(error), "{:?}: {}", args, err);
+        }
+    }
+
+    #[test]
+    fn rejects_roms_that_dont_fit() {
+        let rom = vec![0; chip8::MAX_ROM_SIZE + 1];
+        let err = resolve_error(&["rom.ch8"], &rom);
+        assert_eq!(
+            err,
+            "ROM is 3585 bytes; at most 3584 fit in memory on this platform"
+        );
+        assert!(resolve(&["rom.ch8", "--no-config", "--no-database"], &rom[1..]).is_ok());
+
+        // Below 0xEA0 with the VIP layout, and lower still with a deeper stack
+        let rom = vec![0; 0xEA0 - 0x200 + 1];
+        let err = resolve_error(&["rom.ch8", "--memory", "vip"], &rom);
+        assert!(
+            err.starts_with("ROM is 3233 bytes; at most 3232 fit below"),
+            "{}",
+            err
+        );
+
+        let rom = vec![0; 0xE80 - 0x200 + 1];
+        assert!(resolve(
+            &["rom.ch8", "--memory", "vip", "--no-config", "--no-database"],
+            &rom
+        )
+        .is_ok());
+        let err = resolve_error(&["rom.ch8", "--memory", "vip", "--stack-depth", "40"], &rom);
+        assert!(err.contains("at most 3200 fit below"), "{}", err);
+    }
+
+    #[test]
+    fn recording_runs_headless() {
+        let no_files = ["--no-config", "--no-database"];
+        let run = resolve(&[&["rom.ch8"][..], &no_files].concat(), &ROM).unwrap();
+        assert!(!run.options.headless);
+
+        let record = [&["rom.ch8", "--record", "out.gif"][..], &no_files].concat();
+        let run = resolve(&record, &ROM).unwrap();
+        assert!(run.options.headless);
+        assert_eq!(run.options.record_path, Some(PathBuf::from("out.gif")));
+
+        let test = resolve(&[&["test", "rom.ch8"][..], &no_files].concat(), &ROM).unwrap();
+        assert!(test.options.headless);
+    }
+}
@@ -0,0 +1,194 @@
+use std::path::{Path, PathBuf};
+
+// Settings that make sense to keep in the config file; the rest (recording, movies, etc.)
+// only apply to a single run. Names and values are the same as the command-line options.
+#[rustfmt::skip]
+const KEYS: [&str; 22] = [
+    "platform", "quirks", "speed", "cpu-hz", "timing", "stack-depth", "memory", "machine-code",
+    "vip-rom", "vip-interpreter", "seed", "keymap", "arrows", "renderer", "status", "palette",
+    "scale", "tone", "waveform", "volume", "mute", "legacy",
+];
+
+// INI-style config file, e.g.:
+//
+//   # Global defaults
+//   keymap = azerty
+//   volume = 40
+//
+//   # Per-ROM overrides, by file name or SHA-1
+//   [pong.ch8]
+//   speed = 7
+//
+//   [0df2789f661358d8f7370e6cf93490c5bcd44b01]
+//   quirks = legacy
+//
+// Later settings win: global defaults, then matching file names, then matching SHA-1s
+// (in file order), then command-line options.
+#[derive(Default)]
+pub struct Config {
+    global: Vec<(String, String)>,
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("Cannot read config '{}': {}", path.display(), err))?;
+        Config::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    // Load the config from its default location, if there is one
+    pub fn load_default() -> Result<Self, String> {
+        match default_path() {
+            Some(path) if path.exists() => Config::load(&path),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Config::default();
+        for (line_num, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(section) = line.strip_prefix('[') {
+                let name = section
+                    .strip_suffix(']')
+                    .ok_or_else(|| format!("line {}: unclosed section header", line_num + 1))?;
+                config.sections.push((name.trim().to_string(), Vec::new()));
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected 'key = value'", line_num + 1))?;
+            let key = key.trim().to_lowercase();
+            if !KEYS.contains(&key.as_str()) {
+                return Err(format!(
+                    "line {}: unknown setting '{}' (expected one of: {})",
+                    line_num + 1,
+                    key,
+                    KEYS.join(", ")
+                ));
+            }
+
+            let setting = (key, value.trim().trim_matches('"').to_string());
+            match config.sections.last_mut() {
+                Some((_, settings)) => settings.push(setting),
+                None => config.global.push(setting),
+            }
+        }
+
+        Ok(config)
+    }
+
+    // Settings for a ROM, in the order they should be applied
+    pub fn settings_for(&self, rom_name: &str, rom_hash: &str) -> Vec<(String, String)> {
+        let mut settings = self.global.clone();
+        let matching = |matches: fn(&str, &str, &str) -> bool| {
+            self.sections
+                .iter()
+                .filter(move |(name, _)| matches(name, rom_name, rom_hash))
+                .flat_map(|(_, settings)| settings.iter().cloned())
+        };
+
+        settings.extend(matching(|name, rom_name, _| name == rom_name));
+        settings.extend(matching(|name, _, rom_hash| {
+            name.eq_ignore_ascii_case(rom_hash)
+        }));
+        settings
+    }
+}
+
+// $XDG_CONFIG_HOME/chip8/config.ini, falling back to ~/.config/chip8/config.ini
+pub fn default_path() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_dir.join("chip8").join("config.ini"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "0df2789f661358d8f7370e6cf93490c5bcd44b01";
+
+    fn pairs(settings: &[(&str, &str)]) -> Vec<(String, String)> {
+        settings
+            .iter()
+            .map(|&(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn applies_hash_sections_after_file_names_after_globals() {
+        // Listed hash first, to show file order doesn't decide between the two kinds
+        let config = Config::parse(&format!(
+            "speed = 10\n\
+             [{}]\n\
+             speed = 30\n\
+             [pong.ch8]\n\
+             speed = 20\n\
+             volume = 40\n\
+             [other.ch8]\n\
+             speed = 99\n",
+            HASH.to_uppercase()
+        ))
+        .unwrap();
+
+        assert_eq!(
+            config.settings_for("pong.ch8", HASH),
+            pairs(&[
+                ("speed", "10"),
+                ("speed", "20"),
+                ("volume", "40"),
+                ("speed", "30"),
+            ])
+        );
+        assert_eq!(
+            config.settings_for("tetris.ch8", "0000"),
+            pairs(&[("speed", "10")])
+        );
+    }
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        let config = Config::parse(
+            "# Global defaults\n\
+             \n\
+             ; also a comment\n\
+             \x20 Keymap = \"azerty\" \n\
+             \n\
+             [ pong.ch8 ]\n\
+             # speed = 1\n\
+             quirks = legacy\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.settings_for("pong.ch8", HASH),
+            pairs(&[("keymap", "azerty"), ("quirks", "legacy")])
+        );
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        for (text, error) in [
+            ("speed = 10\nwarp = 9", "line 2: unknown setting 'warp'"),
+            ("[pong.ch8\nspeed = 10", "line 1: unclosed section header"),
+            ("speed 10", "line 1: expected 'key = value'"),
+            // Only kept for a single run, so not allowed here
+            ("record = out.gif", "line 1: unknown setting 'record'"),
+        ] {
+            match Config::parse(text) {
+                Err(err) => assert!(err.starts_with(error), "{}", err),
+                Ok(_) => panic!("{:?} parsed", text),
+            }
+        }
+    }
+}
@@ -1,26 +1,170 @@
 mod chip8;
+mod cli;
+mod config;
+mod json;
+mod romdb;
 
-use std::env::args;
-use std::fs::File;
-use std::io::BufReader;
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::process::exit;
 
 fn main() {
-    let args: Vec<String> = args().collect();
-    if args.len() == 1 || args.len() > 4 {
-        eprintln!("USAGE: cargo run [--release] -- <ROM path> [--legacy] [--debug]");
-        return;
+    chip8::install_handlers();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = cli::parse(&args).unwrap_or_else(|err| {
+        eprintln!(
+            "error: {}\n\n{}\n\nRun 'chip8 --help' for all options.",
+            err,
+            cli::USAGE
+        );
+        exit(2);
+    });
+
+    let result = match command {
+        cli::Command::Run(run_command) => run(run_command, false),
+        cli::Command::Test(run_command) => run(run_command, true),
+        cli::Command::Disasm { rom } => disasm(&rom),
+        cli::Command::Asm { source, output } => asm(&source, &output),
+        cli::Command::Info { rom } => info(&rom),
+        cli::Command::Help => {
+            print!("{}", cli::help());
+            Ok(())
+        }
+        cli::Command::Version => {
+            println!("chip8 {}", env!("CARGO_PKG_VERSION"));
+            Ok(())
+        }
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
+}
+
+fn read_rom(path: &Path) -> Result<Vec<u8>, String> {
+    let rom = std::fs::read(path)
+        .map_err(|err| format!("Cannot open ROM '{}': {}", path.display(), err))?;
+    if rom.len() > chip8::MAX_ROM_SIZE {
+        return Err(format!(
+            "ROM is {} bytes; at most {} fit in memory",
+            rom.len(),
+            chip8::MAX_ROM_SIZE
+        ));
+    }
+
+    Ok(rom)
+}
+
+fn run(run_command: cli::RunCommand, is_test: bool) -> Result<(), String> {
+    let rom = read_rom(&run_command.rom)?;
+    let run_args = run_command.resolve(&rom)?;
+    let mut vm = chip8::VM::new(rom, run_args.options);
+
+    // The panic hook has already restored the terminal and printed the error
+    let result = match panic::catch_unwind(AssertUnwindSafe(|| vm.run())) {
+        Ok(result) => result,
+        Err(_) => {
+            eprintln!("\nVM state:\n{}", vm.state_summary());
+            exit(101);
+        }
+    };
+
+    // Leave the alternate screen before reporting
+    let summary = vm.pacing_summary();
+    let state = vm.state_summary();
+    let screen = is_test.then(|| (vm.screen_hash(), vm.screen_text()));
+    drop(vm);
+    let _ = io::stdout().flush();
+
+    if let Some(summary) = summary {
+        eprintln!("{}", summary);
+    }
+
+    if let Err(err) = result {
+        return Err(format!("{}\n\nVM state:\n{}", err, state));
     }
 
-    // Many ROMs expect slightly different implementations for some opcodes.
-    // Legacy mode enforces the original CHIP-8 specification, in lieu of modern interpretations.
-    let legacy_mode = args.contains(&String::from("--legacy"));
+    if let Some(signal) = chip8::received_signal() {
+        eprintln!(
+            "Stopped by {}\n\nVM state:\n{}",
+            chip8::signal_name(signal),
+            state
+        );
+        exit(128 + signal);
+    }
+
+    if let Some((screen_hash, screen_text)) = screen {
+        print!("{}", screen_text);
+        println!("Screen SHA-1: {}", screen_hash);
+
+        if let Some(expected) = run_args.expect {
+            if expected != screen_hash {
+                return Err(format!("Expected screen SHA-1 {}", expected));
+            }
+        }
+    }
 
-    // Debug mode allows stepping through the ROM instruction-by-instruction,
-    // displaying the emulator's current state (memory, registers, etc.).
-    let debug_mode = args.contains(&String::from("--debug"));
+    Ok(())
+}
+
+fn disasm(path: &Path) -> Result<(), String> {
+    let rom = read_rom(path)?;
+    for line in chip8::disassemble(&rom, chip8::ROM_START) {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+fn asm(source_path: &Path, output_path: &Path) -> Result<(), String> {
+    let source = std::fs::read_to_string(source_path)
+        .map_err(|err| format!("Cannot read '{}': {}", source_path.display(), err))?;
+    let rom = chip8::assemble(&source, chip8::ROM_START)?;
+    if rom.len() > chip8::MAX_ROM_SIZE {
+        return Err(format!("Assembled ROM is too big ({} bytes)", rom.len()));
+    }
+
+    std::fs::write(output_path, &rom)
+        .map_err(|err| format!("Cannot write '{}': {}", output_path.display(), err))?;
+    println!("Wrote {} bytes to {}", rom.len(), output_path.display());
+    Ok(())
+}
+
+fn info(path: &Path) -> Result<(), String> {
+    let rom = read_rom(path)?;
+    println!("File:  {}", path.display());
+    println!(
+        "Size:  {} bytes ({} free)",
+        rom.len(),
+        chip8::MAX_ROM_SIZE - rom.len()
+    );
+    let rom_hash = chip8::rom_hash(&rom);
+    println!("SHA-1: {}", rom_hash);
+
+    let database = romdb::Database::load_default()?;
+    let rom_info = match database.lookup(&rom_hash) {
+        Some(rom_info) => rom_info,
+        None => {
+            println!("\nNot in the ROM database");
+            return Ok(());
+        }
+    };
+
+    println!("\nTitle: {}", rom_info.title);
+    if !rom_info.authors.is_empty() {
+        println!("By:    {}", rom_info.authors.join(", "));
+    }
+
+    if let Some(release) = rom_info.release {
+        println!("Year:  {}", release);
+    }
+
+    println!("For:   {}", rom_info.platforms.join(", "));
+    for (name, value) in rom_info.settings {
+        println!("       --{} {}", name, value);
+    }
 
-    let rom_file = File::open(&args[1]).expect("Cannot open ROM file!");
-    let rom_reader = BufReader::new(rom_file);
-    let vm = chip8::VM::new(rom_reader, legacy_mode, debug_mode);
-    vm.run();
+    Ok(())
 }
@@ -0,0 +1,326 @@
+use std::path::{Path, PathBuf};
+
+use crate::json::{self, Value};
+
+// A local copy of the community CHIP-8 database (https://github.com/chip-8/chip-8-database):
+// the sha1-hashes.json, programs.json and platforms.json files from its database directory
+const FILES: [&str; 3] = ["sha1-hashes.json", "programs.json", "platforms.json"];
+
+// The copy in the source tree's database directory, built in for when there's no local copy
+const BUNDLED: [&str; 3] = [
+    include_str!("../database/sha1-hashes.json"),
+    include_str!("../database/programs.json"),
+    include_str!("../database/platforms.json"),
+];
+
+pub struct Database {
+    hashes: Value,    // SHA-1 -> index into programs
+    programs: Value,  // Title, authors, etc., and settings for each known ROM file
+    platforms: Value, // Quirks and default speed of each platform
+}
+
+// What the database knows about a ROM
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub release: Option<String>,
+    pub platforms: Vec<String>,
+    // Options to run the ROM with, named as on the command line
+    pub settings: Vec<(String, String)>,
+}
+
+impl Database {
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let mut values = Vec::with_capacity(FILES.len());
+        for file in FILES {
+            let path = dir.join(file);
+            let text = std::fs::read_to_string(&path)
+                .map_err(|err| format!("Cannot read '{}': {}", path.display(), err))?;
+            values.push(json::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))?);
+        }
+
+        Ok(Database::from_values(values))
+    }
+
+    pub fn bundled() -> Self {
+        let values = FILES
+            .iter()
+            .zip(BUNDLED)
+            .map(|(file, text)| {
+                json::parse(text)
+                    .unwrap_or_else(|err| panic!("Built-in ROM database: {}: {}", file, err))
+            })
+            .collect();
+
+        Database::from_values(values)
+    }
+
+    // Parsed FILES, in order
+    fn from_values(mut values: Vec<Value>) -> Self {
+        let platforms = values.pop().unwrap();
+        let programs = values.pop().unwrap();
+        let hashes = values.pop().unwrap();
+        Database {
+            hashes,
+            programs,
+            platforms,
+        }
+    }
+
+    // Load the local copy of the database if there is one, otherwise use the built-in one
+    pub fn load_default() -> Result<Self, String> {
+        match default_dir().filter(|dir| dir.join(FILES[0]).exists()) {
+            Some(dir) => Database::load(&dir),
+            None => Ok(Database::bundled()),
+        }
+    }
+
+    pub fn lookup(&self, rom_hash: &str) -> Option<RomInfo> {
+        let index = self.hashes.get(rom_hash)?.as_u64()? as usize;
+        let program = self.programs.as_array().get(index)?;
+        let rom = program.get("roms")?.get(rom_hash)?;
+
+        let strings = |value: Option<&Value>| -> Vec<String> {
+            value
+                .map(Value::as_array)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|item| item.as_str().map(String::from))
+                .collect()
+        };
+
+        let platforms = strings(rom.get("platforms"));
+        let mut settings = Vec::new();
+
+        // The first platform listed is the one the ROM runs best on
+        let platform = platforms.first().and_then(|id| {
+            self.platforms
+                .as_array()
+                .iter()
+                .find(|platform| platform.get("id").and_then(Value::as_str) == Some(id))
+        });
+
+        if let Some(platform) = platform {
+            let overrides = rom
+                .get("quirkyPlatforms")
+                .and_then(|quirky| quirky.get(&platforms[0]));
+            let quirk = |name: &str| {
+                overrides
+                    .and_then(|overrides| overrides.get(name))
+                    .or_else(|| platform.get("quirks")?.get(name))
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false)
+            };
+
+            settings.push((String::from("quirks"), quirks_setting(quirk)));
+        }
+
+        // CHIP-8X ROMs need its opcodes and memory map, not just different quirks
+        if platforms.first().map(String::as_str) == Some("chip8x") {
+            settings.push((String::from("platform"), String::from("chip8x")));
+        }
+
+        if let Some(depth) = platforms.first().and_then(|id| stack_depth(id)) {
+            settings.push((String::from("stack-depth"), depth.to_string()));
+        }
+
+        let tickrate = rom
+            .get("tickrate")
+            .or_else(|| platform?.get("defaultTickrate"))
+            .and_then(Value::as_u64);
+        if let Some(tickrate) = tickrate.filter(|&tickrate| tickrate > 0) {
+            settings.push((String::from("speed"), tickrate.to_string()));
+        }
+
+        // Only the first two colors apply; the rest are for XO-CHIP's extra planes
+        let colors = strings(rom.get("colors").and_then(|colors| colors.get("pixels")));
+        if let [off, on, ..] = colors.as_slice() {
+            if let (Some(off), Some(on)) = (hex_color(off), hex_color(on)) {
+                settings.push((String::from("palette"), format!("{},{}", off, on)));
+            }
+        }
+
+        let keys = rom.get("keys");
+        let arrows: Option<Vec<String>> = ["up", "down", "left", "right"]
+            .iter()
+            .map(|name| Some(format!("{:X}", keys?.get(name)?.as_u64()?)))
+            .collect();
+        if let Some(arrows) = arrows {
+            settings.push((String::from("arrows"), arrows.join(",")));
+        }
+
+        Some(RomInfo {
+            title: program
+                .get("title")
+                .and_then(Value::as_str)
+                .unwrap_or("Untitled")
+                .to_string(),
+            authors: strings(program.get("authors")),
+            release: program
+                .get("release")
+                .and_then(Value::as_str)
+                .map(String::from),
+            platforms,
+            settings,
+        })
+    }
+}
+
+// The database describes each quirk by its modern behavior, e.g. "shift" means 8XY6/8XYE
+// ignore Vy, whereas our flags turn on the original COSMAC VIP behavior. "vblank", "wrap" and
+// "jump" mean the same as ours.
+fn quirks_setting(quirk: impl Fn(&str) -> bool) -> String {
+    let mut enabled = Vec::new();
+    if !quirk("shift") {
+        enabled.push("shift");
+    }
+
+    if !quirk("memoryLeaveIUnchanged") && !quirk("memoryIncrementByX") {
+        enabled.push("load-store");
+    }
+
+    if quirk("vblank") {
+        enabled.push("display-wait");
+    }
+
+    if quirk("wrap") {
+        enabled.push("wrap");
+    }
+
+    if quirk("jump") {
+        enabled.push("jump");
+    }
+
+    if enabled.is_empty() {
+        String::from("none")
+    } else {
+        enabled.join(",")
+    }
+}
+
+// Later platforms allow deeper calls than the VIP's 12. XO-CHIP (Octo) sets no limit, so
+// give its ROMs plenty of room.
+fn stack_depth(platform_id: &str) -> Option<usize> {
+    match platform_id {
+        "chip48" | "superchip1" | "superchip" | "megachip8" => Some(16),
+        "xochip" => Some(256),
+        _ => None,
+    }
+}
+
+// "#RRGGBB" -> "RRGGBB"
+fn hex_color(color: &str) -> Option<&str> {
+    let hex = color.strip_prefix('#')?;
+    (hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit())).then_some(hex)
+}
+
+// $XDG_DATA_HOME/chip8/database, falling back to ~/.local/share/chip8/database
+pub fn default_dir() -> Option<PathBuf> {
+    let data_dir = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+    };
+
+    Some(data_dir.join("chip8").join("database"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database() -> Database {
+        let parse = |text: &str| json::parse(text).unwrap();
+        Database {
+            hashes: parse(r#"{"0123": 0}"#),
+            programs: parse(
+                r##"[{
+                    "title": "Test",
+                    "authors": ["A", "B"],
+                    "roms": {"0123": {
+                        "platforms": ["originalChip8"],
+                        "quirkyPlatforms": {"originalChip8": {"wrap": true}},
+                        "colors": {"pixels": ["#000000", "#ff8800"]},
+                        "keys": {"up": 5, "down": 8, "left": 7, "right": 9}
+                    }}
+                }]"##,
+            ),
+            platforms: parse(
+                r#"[{
+                    "id": "originalChip8",
+                    "defaultTickrate": 15,
+                    "quirks": {"shift": false, "memoryIncrementByX": false, "vblank": true}
+                }]"#,
+            ),
+        }
+    }
+
+    #[test]
+    fn looks_up_settings_for_a_known_rom() {
+        let info = database().lookup("0123").unwrap();
+        assert_eq!(info.title, "Test");
+        assert_eq!(info.authors, ["A", "B"]);
+        assert_eq!(info.platforms, ["originalChip8"]);
+
+        let settings: Vec<(&str, &str)> = info
+            .settings
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            settings,
+            [
+                ("quirks", "shift,load-store,display-wait,wrap"),
+                ("speed", "15"),
+                ("palette", "000000,ff8800"),
+                ("arrows", "5,8,7,9"),
+            ]
+        );
+    }
+
+    #[test]
+    fn later_platforms_allow_deeper_calls() {
+        let mut later = database();
+        for (platform, depth) in [("superchip", Some("16")), ("xochip", Some("256"))] {
+            later.programs = json::parse(&format!(
+                r#"[{{"roms": {{"0123": {{"platforms": ["{}"]}}}}}}]"#,
+                platform
+            ))
+            .unwrap();
+
+            let info = later.lookup("0123").unwrap();
+            let setting = info
+                .settings
+                .iter()
+                .find(|(name, _)| name == "stack-depth")
+                .map(|(_, value)| value.as_str());
+            assert_eq!(setting, depth, "{}", platform);
+        }
+
+        let info = database().lookup("0123").unwrap();
+        assert!(info.settings.iter().all(|(name, _)| name != "stack-depth"));
+    }
+
+    #[test]
+    fn bundled_platforms_configure_roms() {
+        let mut bundled = Database::bundled();
+        bundled.hashes = json::parse(r#"{"0123": 0}"#).unwrap();
+        bundled.programs =
+            json::parse(r#"[{"title": "Test", "roms": {"0123": {"platforms": ["superchip"]}}}]"#)
+                .unwrap();
+
+        let info = bundled.lookup("0123").unwrap();
+        assert_eq!(
+            info.settings,
+            [
+                (String::from("quirks"), String::from("jump")),
+                (String::from("stack-depth"), String::from("16")),
+                (String::from("speed"), String::from("30")),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_rom() {
+        assert!(database().lookup("4567").is_none());
+    }
+}